@@ -0,0 +1,191 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use crate::data::Release;
+use crate::errors::{Error, Reason, Result};
+
+// 编译源码所需的最低自举版本，与各版本make.bash中的要求一致，go1.4及以前由C编译，不需要自举
+// go1.22起每两个版本提高一次，要求的是再早两个版本的.6，如go1.24、go1.25需要go1.22.6
+pub fn bootstrap_requirement(target: &Release) -> Option<Release> {
+    let minor = match (target.v1, target.v2.unwrap_or_default()) {
+        (1, n) if n >= 22 => (n - 2 - n % 2, Some(6)),
+        (1, 20 | 21) => (17, Some(13)),
+        (1, n) if n >= 5 => (4, None),
+        _ => return None,
+    };
+
+    Some(Release {
+        v1: 1,
+        v2: Some(minor.0),
+        v3: minor.1,
+        unstable_v4: None,
+    })
+}
+
+// 从候选中选出满足要求的最新稳定版本
+pub fn select_bootstrap<'a, T>(
+    target: &Release,
+    candidates: &'a [T],
+    release: impl Fn(&T) -> Option<Release>,
+) -> Option<&'a T> {
+    let requirement = bootstrap_requirement(target)?;
+
+    candidates
+        .iter()
+        .flat_map(|x| release(x).map(|r| (r, x)))
+        .filter(|x| x.0.unstable_v4.is_none() && x.0 >= requirement)
+        .max_by(|x, y| x.0.cmp(&y.0))
+        .map(|x| x.1)
+}
+
+// 没有VERSION文件的开发版本源码对应的版本，来自src/internal/goversion/goversion.go，如go1.24
+pub fn devel_release(dir: &Path) -> Option<Release> {
+    let text = fs::read_to_string(dir.join("src/internal/goversion/goversion.go")).ok()?;
+    let minor = text
+        .lines()
+        .find_map(|x| x.trim().strip_prefix("const Version = "))?
+        .trim()
+        .parse()
+        .ok()?;
+
+    Some(Release {
+        v1: 1,
+        v2: Some(minor),
+        v3: None,
+        unstable_v4: None,
+    })
+}
+
+// 开发版本的名称，与go version报告的devel go1.24-abc1234对应，不会被当作发布版本匹配
+pub fn devel_name(dir: &Path, release: &Release) -> String {
+    let hash = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .current_dir(dir)
+        .output()
+        .ok()
+        .filter(|x| x.status.success())
+        .map(|x| String::from_utf8_lossy(&x.stdout).trim().to_string())
+        .filter(|x| !x.is_empty());
+
+    match hash {
+        Some(x) => format!("devel-{release}-{x}"),
+        None => format!("devel-{release}"),
+    }
+}
+
+// 在goroot/src中执行make.bash，bootstrap为None时不设置GOROOT_BOOTSTRAP
+pub fn make(goroot: &Path, bootstrap: Option<&Path>) -> Result<()> {
+    let mut cmd = Command::new("bash");
+    cmd.arg("make.bash")
+        .current_dir(goroot.join("src"))
+        // 编译结果由所在位置确定GOROOT，不能使用环境中的设置
+        .env_remove("GOROOT")
+        .env_remove("GOROOT_FINAL")
+        .env_remove("GOBIN")
+        .env("GOTOOLCHAIN", "local");
+    if let Some(x) = bootstrap {
+        cmd.env("GOROOT_BOOTSTRAP", x);
+    }
+
+    let status = cmd.status()?;
+    if !status.success() {
+        return Err(Error {
+            kind: Reason::BuildFailed,
+            msg: format!("make.bash执行失败: {status}"),
+        });
+    }
+
+    Ok(())
+}
+
+// 在goroot中应用补丁，补丁中的路径相对于goroot
+pub fn apply_patch(goroot: &Path, patch: &Path) -> Result<()> {
+    // -d会先切换目录，补丁需要使用绝对路径
+    let patch = patch.canonicalize()?;
+    let status = Command::new("patch")
+        .args(["-p1", "--forward", "--batch", "-s", "-d"])
+        .arg(goroot)
+        .arg("-i")
+        .arg(&patch)
+        .status()?;
+    if !status.success() {
+        return Err(Error {
+            kind: Reason::BuildFailed,
+            msg: format!("应用补丁{}失败: {status}", patch.display()),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::build::{bootstrap_requirement, devel_name, devel_release, select_bootstrap};
+    use crate::data::Release;
+    use std::fs;
+
+    #[test]
+    fn requirement() {
+        let requirement = |x: &str| bootstrap_requirement(&Release::parse(x).unwrap());
+
+        assert_eq!(requirement("1.22.3"), Release::parse("1.20.6"));
+        assert_eq!(requirement("1.23.0"), Release::parse("1.20.6"));
+        assert_eq!(requirement("1.24rc1"), Release::parse("1.22.6"));
+        assert_eq!(requirement("1.26.0"), Release::parse("1.24.6"));
+        assert_eq!(requirement("1.21.3"), Release::parse("1.17.13"));
+        assert_eq!(requirement("1.19.5"), Release::parse("1.4"));
+        assert_eq!(requirement("1.4.3"), None);
+    }
+
+    #[test]
+    fn select() {
+        let installed = [
+            "go1.17.12",
+            "go1.17.13",
+            "go1.20.5",
+            "go1.21.3",
+            "go1.22rc1",
+            "go1.22.3",
+        ];
+        let select = |x: &str| {
+            select_bootstrap(&Release::parse(x).unwrap(), &installed, |x| {
+                Release::parse(x)
+            })
+            .copied()
+        };
+
+        assert_eq!(select("1.21.3"), Some("go1.22.3"));
+        assert_eq!(select("1.23.0"), Some("go1.22.3"));
+        // 只有go1.22.0至go1.22.5时不满足go1.24的要求
+        assert_eq!(select("1.24.0"), None);
+        assert_eq!(select("1.26.0"), None);
+
+        let old = ["go1.17.13", "go1.20.5"];
+        let select = |x: &str| {
+            select_bootstrap(&Release::parse(x).unwrap(), &old, |x| Release::parse(x)).copied()
+        };
+        assert_eq!(select("1.21.3"), Some("go1.20.5"));
+        assert_eq!(select("1.22.3"), None);
+    }
+
+    #[test]
+    fn devel() {
+        let dir = std::env::temp_dir().join(format!("pgvm_devel_{}", std::process::id()));
+        let file = dir.join("src/internal/goversion/goversion.go");
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(
+            &file,
+            "package goversion\n\n// comment\nconst Version = 24\n",
+        )
+        .unwrap();
+
+        let release = devel_release(&dir).unwrap();
+        assert_eq!(release, Release::parse("1.24").unwrap());
+        let name = devel_name(&dir, &release);
+        assert!(name.starts_with("devel-go1.24"));
+        assert_eq!(Release::parse(&name), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,170 @@
+use openssl::sha::Sha256;
+use serde::{Deserialize, Serialize};
+use tar::{Archive, Builder, EntryType, Header, HeaderMode};
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::common::WriteSha256;
+use crate::errors::{Error, Reason, Result};
+
+// 打包文件中的清单，位于GOROOT之前，不需要解压整个文件即可读取
+pub const MANIFEST: &str = "pgvm-manifest.json";
+// 打包文件的扩展名
+pub const EXTENSION: &str = "tar.zst";
+
+// 打包的已安装版本，files为GOROOT中普通文件的相对路径到sha256的映射
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Manifest {
+    // 安装目录中的名称，如go1.22.3+ourfix
+    pub version: String,
+    pub os: String,
+    pub arch: String,
+    // 所有文件的大小
+    pub size: u64,
+    pub files: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    // 计算goroot中每个文件的hash，符号链接不会被跟随
+    pub fn collect(goroot: &Path, version: &str, os: &str, arch: &str) -> Result<Self> {
+        let mut manifest = Self {
+            version: version.to_string(),
+            os: os.to_string(),
+            arch: arch.to_string(),
+            size: 0,
+            files: BTreeMap::new(),
+        };
+        manifest.walk(goroot, "")?;
+
+        Ok(manifest)
+    }
+
+    fn walk(&mut self, dir: &Path, prefix: &str) -> Result<()> {
+        for x in fs::read_dir(dir)? {
+            let x = x?;
+            let name = format!("{prefix}{}", x.file_name().to_string_lossy());
+            let meta = x.file_type()?;
+            if meta.is_dir() {
+                self.walk(&x.path(), &format!("{name}/"))?;
+            } else if meta.is_file() {
+                let mut hasher = WriteSha256::new(Sha256::new());
+                self.size += io::copy(&mut File::open(x.path())?, &mut hasher)?;
+                self.files
+                    .insert(name, hex::encode(hasher.into_sha256().finish()));
+            }
+        }
+
+        Ok(())
+    }
+
+    // 检查解压出的goroot与清单中的文件一致
+    pub fn verify(&self, goroot: &Path) -> Result<()> {
+        let actual = Self::collect(goroot, &self.version, &self.os, &self.arch)?;
+
+        let missing = self.files.keys().find(|x| !actual.files.contains_key(*x));
+        let changed = actual
+            .files
+            .iter()
+            .find(|(k, v)| self.files.get(*k) != Some(*v));
+        if let Some(x) = missing.or_else(|| changed.map(|x| x.0)) {
+            return Err(Error {
+                kind: Reason::Hashinconformity,
+                msg: format!("{x}与清单不一致"),
+            });
+        }
+
+        Ok(())
+    }
+
+    // 将清单与goroot写入w，goroot在打包文件中为go目录
+    pub fn pack<W: Write>(&self, goroot: &Path, w: W) -> Result<()> {
+        let mut builder = Builder::new(zstd::Encoder::new(w, 0)?);
+        // 不记录本机的用户与时间，相同的内容得到相同的打包文件
+        builder.mode(HeaderMode::Deterministic);
+        builder.follow_symlinks(false);
+
+        let data = serde_json::to_vec_pretty(self)?;
+        let mut header = Header::new_gnu();
+        header.set_entry_type(EntryType::Regular);
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, MANIFEST, data.as_slice())?;
+
+        builder.append_dir_all("go", goroot)?;
+        builder.into_inner()?.finish()?.flush()?;
+
+        Ok(())
+    }
+}
+
+// 读取打包文件开头的清单
+pub fn read_manifest<R: Read>(r: R) -> Result<Manifest> {
+    let mut archive = Archive::new(zstd::Decoder::new(r)?);
+    let mut entry = match archive.entries()?.next() {
+        Some(x) => x?,
+        None => {
+            return Err(Error {
+                kind: Reason::InvalidResource,
+                msg: String::from("打包文件为空"),
+            })
+        }
+    };
+    if entry.path()?.as_ref() != Path::new(MANIFEST) {
+        return Err(Error {
+            kind: Reason::InvalidResource,
+            msg: format!("打包文件中缺少{MANIFEST}"),
+        });
+    }
+
+    let mut data = Vec::new();
+    entry.read_to_end(&mut data)?;
+
+    Ok(serde_json::from_slice(&data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bundle::{read_manifest, Manifest};
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::process;
+
+    #[test]
+    fn pack() {
+        let dir = std::env::temp_dir().join(format!("pgvm_bundle_{}", process::id()));
+        let goroot = dir.join("go");
+        fs::create_dir_all(goroot.join("bin")).unwrap();
+        fs::write(goroot.join("VERSION"), "go1.22.3\n").unwrap();
+        fs::write(goroot.join("bin").join("go"), "go").unwrap();
+        symlink("bin/go", goroot.join("go")).unwrap();
+
+        let manifest = Manifest::collect(&goroot, "go1.22.3+ourfix", "linux", "amd64").unwrap();
+        assert_eq!(manifest.size, 11);
+        assert_eq!(
+            manifest.files.keys().collect::<Vec<_>>(),
+            ["VERSION", "bin/go"]
+        );
+
+        let mut buf = Vec::new();
+        manifest.pack(&goroot, &mut buf).unwrap();
+        assert_eq!(read_manifest(buf.as_slice()).unwrap(), manifest);
+
+        let mut archive = tar::Archive::new(zstd::Decoder::new(buf.as_slice()).unwrap());
+        archive.unpack(dir.join("out")).unwrap();
+        manifest.verify(&dir.join("out").join("go")).unwrap();
+        assert!(dir
+            .join("out/go/go")
+            .symlink_metadata()
+            .unwrap()
+            .is_symlink());
+
+        fs::write(dir.join("out/go/VERSION"), "go1.22.4\n").unwrap();
+        assert!(manifest.verify(&dir.join("out").join("go")).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,17 +1,27 @@
 use clap::{Args, Parser, Subcommand, ValueEnum};
+use pgvm::xdg::BaseDir;
 use static_init::dynamic;
+use std::ffi::OsString;
 use std::path::PathBuf;
 
 const DATABASE_PATH_NAME: &str = "PGVM_DATABASE_PATH";
 const DOWNLOAD_PATH_NAME: &str = "PGVM_DOWNLOAD_PATH";
 const INSTALL_PATH_NAME: &str = "PGVM_INSTALL_PATH";
+const SYSTEM_PATH_NAME: &str = "PGVM_SYSTEM_PATH";
 
+// 无法确定默认位置时为空，没有通过参数指定时再报告原因
 #[dynamic]
-static DEFAULT_DATABASE_PATH: PathBuf = dirs::config_dir().unwrap().join("pgvm");
+pub static DEFAULT_DATABASE_PATH: PathBuf = default_path(BaseDir::Config);
 #[dynamic]
-static DEFAULT_DOWNLOAD_PATH: PathBuf = dirs::download_dir().unwrap().join("pgvm");
+pub static DEFAULT_DOWNLOAD_PATH: PathBuf = default_path(BaseDir::Cache);
 #[dynamic]
-static DEFAULT_INSTALL_PATH: PathBuf = PathBuf::from("/usr/local/share/go");
+static DEFAULT_INSTALL_PATH: PathBuf = default_path(BaseDir::Data);
+#[dynamic]
+static DEFAULT_SYSTEM_PATH: PathBuf = PathBuf::from("/usr/local/share/go");
+
+pub fn default_path(dir: BaseDir) -> PathBuf {
+    dir.path().map(|x| x.join("pgvm")).unwrap_or_default()
+}
 
 /// pgvm golang 版本管理工具
 #[derive(Parser, Debug)]
@@ -26,9 +36,12 @@ pub struct Cli {
     /// 下载安装包存放位置
     #[clap(long, value_parser, env = DOWNLOAD_PATH_NAME, default_value_os_t = DEFAULT_DOWNLOAD_PATH.clone())]
     pub download_path: PathBuf,
-    /// golang安装位置
+    /// golang安装位置，当前用户可写
     #[clap(long, value_parser, env = INSTALL_PATH_NAME, default_value_os_t = DEFAULT_INSTALL_PATH.clone())]
     pub install_path: PathBuf,
+    /// 系统的golang安装位置，由管理员维护，与install_path中的版本一起使用
+    #[clap(long, value_parser, env = SYSTEM_PATH_NAME, default_value_os_t = DEFAULT_SYSTEM_PATH.clone())]
+    pub system_path: PathBuf,
     #[clap(subcommand)]
     pub command: Option<Commands>,
 }
@@ -41,6 +54,28 @@ pub enum Commands {
     Install(Install),
     /// 切换版本
     Switch(Switch),
+    /// 生成shim，或以shim方式执行程序
+    Shim(Shim),
+    /// 输出激活go版本的shell代码
+    Env(Env),
+    /// 使用指定版本执行命令，未安装时先安装
+    Exec(Exec),
+    /// 显示当前使用的版本及其来源
+    Current,
+    /// 显示程序在当前版本中的路径
+    Which(Which),
+    /// 管理版本别名
+    Alias(Alias),
+    /// 卸载已安装的版本
+    Uninstall(Uninstall),
+    /// 收录已有的go安装
+    Adopt(Adopt),
+    /// 管理~/sdk中golang.org/dl格式的链接
+    Sdk(Sdk),
+    /// 以指定名称注册已有的GOROOT，不复制文件
+    Link(Link),
+    /// 将已安装的版本打包，可在其他机器上通过install --file安装
+    Pack(Pack),
 }
 
 #[derive(Args, Debug)]
@@ -54,6 +89,9 @@ pub struct List {
     /// 选择查看类型
     #[clap(long, value_parser, value_enum, default_value_t)]
     pub mode: ShowMode,
+    /// 只显示已安装的版本
+    #[clap(long, value_parser)]
+    pub installed: bool,
 }
 
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Default, ValueEnum)]
@@ -68,6 +106,38 @@ pub enum ShowMode {
 pub struct Install {
     #[clap(value_parser)]
     pub version: Option<String>,
+    /// 安装当前项目go.mod或go.work要求的版本
+    #[clap(long, value_parser, conflicts_with = "version")]
+    pub from_project: bool,
+    /// 从本地的安装包或pack生成的打包文件安装，如go1.21.3.linux-amd64.tar.gz
+    #[clap(long, value_parser, conflicts_with_all = &["version", "from-project"])]
+    pub file: Option<PathBuf>,
+    /// 本地安装包的sha256
+    #[clap(long, value_parser, requires = "file")]
+    pub sha256: Option<String>,
+    /// 从源码编译安装，可以是版本、源码包或源码目录
+    #[clap(
+        long,
+        value_parser,
+        value_name = "VERSION|PATH",
+        conflicts_with_all = &["version", "from-project", "file"]
+    )]
+    pub from_source: Option<String>,
+    /// 编译前应用的补丁，可以指定多次，按顺序应用
+    #[clap(long, value_parser, requires = "from-source")]
+    pub patch: Vec<PathBuf>,
+    /// 打补丁版本的名称后缀，如ourfix对应go1.22.3+ourfix，默认使用补丁的hash
+    #[clap(long, value_parser, requires = "patch")]
+    pub suffix: Option<String>,
+    /// 重新安装已安装的版本
+    #[clap(short, long, value_parser)]
+    pub force: bool,
+    /// 不保留下载的安装包
+    #[clap(long, value_parser)]
+    pub no_cache: bool,
+    /// 安装到系统安装位置，需要有写入权限
+    #[clap(long, value_parser)]
+    pub system: bool,
 }
 
 #[derive(Args, Debug)]
@@ -75,3 +145,160 @@ pub struct Switch {
     /// 切换版本
     pub version: Option<String>,
 }
+
+#[derive(Args, Debug)]
+#[clap(trailing_var_arg = true)]
+pub struct Shim {
+    /// 执行的程序名，为空时重新生成shim
+    pub name: Option<String>,
+    /// 传递给程序的参数
+    #[clap(value_parser, allow_hyphen_values = true, multiple_values = true)]
+    pub args: Vec<OsString>,
+}
+
+#[derive(Args, Debug)]
+pub struct Env {
+    /// go版本，为空时使用当前版本
+    pub version: Option<String>,
+    /// shell类型，为空时根据SHELL环境变量推断
+    #[clap(long, value_parser, value_enum)]
+    pub shell: Option<ShellKind>,
+    /// 以json格式输出
+    #[clap(long, value_parser)]
+    pub json: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct Adopt {
+    /// 已有的GOROOT，如/usr/local/go
+    #[clap(value_parser, required_unless_present = "scan")]
+    pub paths: Vec<PathBuf>,
+    /// 查找常见位置中的go安装，如/usr/local/go、/usr/lib/go*、~/sdk/go*
+    #[clap(long, value_parser)]
+    pub scan: bool,
+    /// 收录方式
+    #[clap(long, value_parser, value_enum, default_value_t = AdoptMode::Reference)]
+    pub mode: AdoptMode,
+    /// 替换已安装的相同版本
+    #[clap(short, long, value_parser)]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct Link {
+    /// 名称，用于switch、exec、env等命令
+    pub name: String,
+    /// 已有的GOROOT，如/opt/go-custom
+    #[clap(value_parser)]
+    pub path: PathBuf,
+    /// 替换同名的版本
+    #[clap(short, long, value_parser)]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct Pack {
+    /// 需要打包的go版本
+    pub version: String,
+    /// 打包文件，需要以.tar.zst结尾，如go.tar.zst
+    #[clap(short, long, value_parser)]
+    pub output: PathBuf,
+    /// 覆盖已存在的打包文件
+    #[clap(short, long, value_parser)]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct Sdk {
+    #[clap(subcommand)]
+    pub command: SdkCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SdkCommand {
+    /// 启用，安装或卸载后自动更新~/sdk中的链接
+    Enable,
+    /// 停用，删除~/sdk中由pgvm创建的链接
+    Disable,
+    /// 立即更新~/sdk中的链接
+    Sync,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum AdoptMode {
+    /// 移动到安装目录中
+    Move,
+    /// 复制到安装目录中
+    Copy,
+    /// 只在安装目录中链接到原位置
+    Reference,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum ShellKind {
+    Bash,
+    Zsh,
+    Fish,
+    Nushell,
+}
+
+#[derive(Args, Debug)]
+#[clap(trailing_var_arg = true)]
+pub struct Exec {
+    /// go版本
+    pub version: String,
+    /// 执行的命令及参数
+    #[clap(
+        value_parser,
+        required = true,
+        allow_hyphen_values = true,
+        multiple_values = true
+    )]
+    pub command: Vec<OsString>,
+}
+
+#[derive(Args, Debug)]
+pub struct Which {
+    /// 程序名，如go、gofmt、vet
+    pub binary: String,
+}
+
+#[derive(Args, Debug)]
+pub struct Alias {
+    #[clap(subcommand)]
+    pub command: AliasCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AliasCommand {
+    /// 设置别名
+    Set {
+        /// 别名
+        name: String,
+        /// 对应的go版本
+        version: String,
+    },
+    /// 删除别名
+    Rm {
+        /// 别名
+        name: String,
+    },
+    /// 列出所有别名
+    List,
+}
+
+#[derive(Args, Debug)]
+pub struct Uninstall {
+    /// 需要卸载的go版本
+    #[clap(value_parser, required = true)]
+    pub versions: Vec<String>,
+    /// 允许卸载正在使用的版本
+    #[clap(short, long, value_parser)]
+    pub force: bool,
+    /// 同时删除下载的安装包
+    #[clap(long, value_parser)]
+    pub purge: bool,
+    /// 允许卸载系统安装位置中的版本
+    #[clap(long, value_parser)]
+    pub system: bool,
+}
@@ -1,6 +1,15 @@
+use crate::errors::{Error, Reason, Result};
+use indicatif::HumanBytes;
 use openssl::sha::Sha256;
+use std::env::consts;
+use std::ffi::CString;
+use std::fs;
 use std::io::Write;
 use std::ops::{Deref, DerefMut};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub struct WriteSha256(Sha256);
 
@@ -38,3 +47,138 @@ impl Write for WriteSha256 {
         Ok(())
     }
 }
+
+// 当前系统对应的go os名称
+pub fn host_os() -> &'static str {
+    match consts::OS {
+        "macos" => "darwin",
+        x => x,
+    }
+}
+
+// 当前系统对应的go arch名称
+pub fn host_arch() -> &'static str {
+    match consts::ARCH {
+        "x86_64" => "amd64",
+        "x86" => "386",
+        "aarch64" => "arm64",
+        "arm" => "armv6l",
+        "loongarch64" => "loong64",
+        "powerpc64" if cfg!(target_endian = "little") => "ppc64le",
+        "powerpc64" => "ppc64",
+        x => x,
+    }
+}
+
+// 当前unix时间戳
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or_default()
+}
+
+// 时间戳t距今多久，如3天前
+pub fn ago(t: u64) -> String {
+    format_ago(unix_now().saturating_sub(t))
+}
+
+fn format_ago(secs: u64) -> String {
+    const UNITS: &[(u64, &str)] = &[
+        (365 * 24 * 3600, "年"),
+        (30 * 24 * 3600, "个月"),
+        (24 * 3600, "天"),
+        (3600, "小时"),
+        (60, "分钟"),
+    ];
+
+    match UNITS.iter().find(|x| secs >= x.0) {
+        Some((n, unit)) => format!("{}{unit}前", secs / n),
+        None => String::from("刚刚"),
+    }
+}
+
+// 目录占用的空间，不跟随符号链接
+pub fn dir_size<P: AsRef<Path>>(path: P) -> std::io::Result<u64> {
+    let meta = fs::symlink_metadata(&path)?;
+    if !meta.is_dir() {
+        return Ok(meta.len());
+    }
+
+    let mut size = 0;
+    for x in fs::read_dir(path)? {
+        size += dir_size(x?.path())?;
+    }
+
+    Ok(size)
+}
+
+// 解压后的大小约为安装包的4倍
+pub const UNPACK_RATIO: u64 = 4;
+
+// 源码包解压并编译后的大小约为源码包的12倍
+pub const BUILD_RATIO: u64 = 12;
+
+// 当前用户是否可以在目录中创建与删除内容
+pub fn writable(path: &Path) -> bool {
+    let path = match CString::new(path.as_os_str().as_bytes()) {
+        Ok(x) => x,
+        Err(_) => return false,
+    };
+
+    unsafe { libc::access(path.as_ptr(), libc::W_OK | libc::X_OK) == 0 }
+}
+
+// 检查各路径所在的文件系统是否有足够的空间，同一文件系统上的需求会累加
+pub fn check_space(needs: &[(&Path, u64)]) -> Result<()> {
+    let mut devices: Vec<(u64, &Path, u64)> = Vec::new();
+    for (path, needed) in needs.iter() {
+        let existing = nearest_existing(path)?;
+        let dev = fs::metadata(existing)?.dev();
+
+        match devices.iter_mut().find(|x| x.0 == dev) {
+            Some(x) => x.2 += needed,
+            None => devices.push((dev, existing, *needed)),
+        }
+    }
+
+    for (_, path, needed) in devices {
+        let available = fs2::available_space(path)?;
+        if available < needed {
+            return Err(Error {
+                kind: Reason::InsufficientDiskSpace,
+                msg: format!(
+                    "{}所在磁盘空间不足，需要{}，可用{}",
+                    path.display(),
+                    HumanBytes(needed),
+                    HumanBytes(available)
+                ),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+// 路径可能还未创建，使用最近的已存在的上级目录
+fn nearest_existing(path: &Path) -> std::io::Result<&Path> {
+    path.ancestors()
+        .find(|x| x.exists())
+        .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::format_ago;
+
+    #[test]
+    fn ago() {
+        assert_eq!(format_ago(0), "刚刚");
+        assert_eq!(format_ago(59), "刚刚");
+        assert_eq!(format_ago(60), "1分钟前");
+        assert_eq!(format_ago(3 * 3600 + 5), "3小时前");
+        assert_eq!(format_ago(2 * 24 * 3600), "2天前");
+        assert_eq!(format_ago(65 * 24 * 3600), "2个月前");
+        assert_eq!(format_ago(800 * 24 * 3600), "2年前");
+    }
+}
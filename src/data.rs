@@ -1,13 +1,42 @@
 use std::cmp::Ordering;
 use std::collections::HashSet;
 use std::fmt::{Debug, Display, Formatter, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use sled::Db as Database;
+use static_init::dynamic;
 
 use crate::db::ExtKv;
 use crate::errors::Result;
+use crate::lock::{lock_path, FileLock};
+
+// 官方发布安装包的os，用于识别旧版本中交换了os与arch的名称
+pub const KNOWN_OS: &[&str] = &[
+    "aix",
+    "android",
+    "darwin",
+    "dragonfly",
+    "freebsd",
+    "illumos",
+    "ios",
+    "linux",
+    "netbsd",
+    "openbsd",
+    "plan9",
+    "solaris",
+    "windows",
+];
+
+// 匹配版本号，允许go前缀及平台或自定义后缀，如1.21、go1.22rc1、go1.21.3.linux-amd64、go1.22.3+ourfix
+#[dynamic]
+static RELEASE_MATCHER: Regex =
+    Regex::new(r#"^(?:go)?(\d+)(?:\.(\d+))?(?:\.(\d+))?(?:(beta|rc)(\d+))?(?:[.+]|$)"#).unwrap();
+
+// 源码包在版本名中的平台部分，如go1.21.3.src
+pub const SOURCE_OS: &str = "src";
 
 #[derive(Default, Deserialize, Serialize)]
 pub struct ProgramState {
@@ -29,6 +58,19 @@ pub struct Version {
 }
 
 impl Version {
+    pub fn release(&self) -> Release {
+        Release {
+            v1: self.v1,
+            v2: self.v2,
+            v3: self.v3,
+            unstable_v4: self.unstable_v4.clone(),
+        }
+    }
+
+    pub fn is_source(&self) -> bool {
+        self.os == SOURCE_OS
+    }
+
     pub fn short_version(&self) -> String {
         let mut s = self.v1.to_string();
         if let Some(x) = self.v2 {
@@ -57,13 +99,17 @@ impl Display for Version {
             write!(f, "{x}")?;
         }
 
+        if self.is_source() {
+            return write!(f, ".{}", self.os);
+        }
+
         write!(f, ".{}-{}", self.os, self.arch)
     }
 }
 
 impl Ord for Version {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        self.release().cmp(&other.release())
     }
 }
 
@@ -78,31 +124,76 @@ impl PartialEq for Version {
 
 impl PartialOrd for Version {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        macro_rules! cmp {
-            ($v1:expr, $v2:expr) => {
-                let x = $v1.partial_cmp($v2);
-                if let Some(x) = x {
-                    if !matches!(x, Ordering::Equal) {
-                        return Some(x);
-                    }
-                }
-            };
-        }
+        Some(self.cmp(other))
+    }
+}
 
-        cmp!(&self.v1, &other.v1);
-        cmp!(&self.v2, &other.v2);
-        cmp!(&self.v3, &other.v3);
+// 已安装版本的记录
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Installation {
+    // 安装目录名，如go1.21.3.linux-amd64
+    pub version: String,
+    pub os: String,
+    pub arch: String,
+    pub path: PathBuf,
+    // 安装时间，unix时间戳
+    pub installed_at: u64,
+    // 安装包的sha256
+    pub sha256: Option<String>,
+    // 占用的磁盘空间
+    pub size: u64,
+    // 最后使用时间，unix时间戳
+    pub last_used: Option<u64>,
+    pub source: InstallSource,
+}
 
-        if self.unstable_v4.is_none() && other.unstable_v4.is_some() {
-            return Some(Ordering::Greater);
-        }
-        if self.unstable_v4.is_some() && other.unstable_v4.is_none() {
-            return Some(Ordering::Less);
-        }
+// 已安装版本的来源
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum InstallSource {
+    // 从官方下载安装
+    Download,
+    // 安装目录中已存在，但没有安装记录
+    Unknown,
+    // 从源码编译安装
+    Source,
+    // 在base的源码上应用补丁后编译安装
+    Patched { base: String, patches: Vec<Patch> },
+    // 收录已有的安装，reference为true时只链接到from
+    Adopted { from: PathBuf, reference: bool },
+    // 以自定义名称链接到target，不属于pgvm管理
+    Linked { target: PathBuf },
+    // 从pack生成的打包文件安装
+    Bundle { file: PathBuf },
+}
 
-        cmp!(&self.unstable_v4.as_ref(), &other.unstable_v4.as_ref());
+// 编译前应用的补丁文件
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Patch {
+    pub path: PathBuf,
+    pub sha256: String,
+}
+
+impl Installation {
+    // 从版本名中获取平台，如go1.21.3.linux-amd64，系统安装位置中未改名的旧名称同样适用
+    pub fn platform_of(version: &str) -> Option<(String, String)> {
+        let version = Self::legacy_name(version).unwrap_or_else(|| version.to_string());
+        let (_, platform) = version.rsplit_once('.')?;
+        let (os, arch) = platform.split_once('-')?;
 
-        Some(Ordering::Equal)
+        Some((os.to_string(), arch.to_string()))
+    }
+
+    // 旧版本解析目录时交换了os与arch，安装目录名为如go1.21.3.amd64-linux，返回修正后的名称，
+    // 只处理发布版本的名称，链接等自定义名称如my.build-linux保持不变
+    pub fn legacy_name(version: &str) -> Option<String> {
+        let (prefix, platform) = version.rsplit_once('.')?;
+        let (arch, os) = platform.split_once('-')?;
+        let release = Release::parse(prefix).filter(|x| x.to_string() == prefix);
+        if release.is_none() || !KNOWN_OS.contains(&os) || KNOWN_OS.contains(&arch) {
+            return None;
+        }
+
+        Some(format!("{prefix}.{os}-{arch}"))
     }
 }
 
@@ -120,7 +211,16 @@ pub enum UnstableVersion {
 
 impl Ord for UnstableVersion {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        match self {
+            UnstableVersion::RC(x) => match other {
+                UnstableVersion::RC(o) => x.cmp(o),
+                UnstableVersion::Beta(_) => Ordering::Greater,
+            },
+            UnstableVersion::Beta(x) => match other {
+                UnstableVersion::RC(_) => Ordering::Less,
+                UnstableVersion::Beta(o) => x.cmp(o),
+            },
+        }
     }
 }
 
@@ -151,42 +251,161 @@ impl Display for UnstableVersion {
 
 impl PartialOrd for UnstableVersion {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match self {
-            UnstableVersion::RC(x) => match other {
-                UnstableVersion::RC(o) => x.cmp(o).into(),
-                UnstableVersion::Beta(_) => Some(Ordering::Greater),
-            },
-            UnstableVersion::Beta(x) => match other {
-                UnstableVersion::RC(_) => Some(Ordering::Less),
-                UnstableVersion::Beta(o) => x.cmp(o).into(),
-            },
+        Some(self.cmp(other))
+    }
+}
+
+// 不带平台信息的版本号
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Release {
+    pub v1: i32,
+    pub v2: Option<i32>,
+    pub v3: Option<i32>,
+    pub unstable_v4: Option<UnstableVersion>,
+}
+
+impl Release {
+    pub fn parse(s: &str) -> Option<Self> {
+        let cap = RELEASE_MATCHER.captures(s.trim())?;
+        let unstable_v4 = match (cap.get(4), cap.get(5)) {
+            (Some(kind), Some(n)) => {
+                let n: i32 = n.as_str().parse().ok()?;
+                match kind.as_str() {
+                    "beta" => Some(UnstableVersion::Beta(n)),
+                    _ => Some(UnstableVersion::RC(n)),
+                }
+            }
+            _ => None,
+        };
+
+        Some(Self {
+            v1: cap.get(1)?.as_str().parse().ok()?,
+            v2: cap.get(2).and_then(|x| x.as_str().parse().ok()),
+            v3: cap.get(3).and_then(|x| x.as_str().parse().ok()),
+            unstable_v4,
+        })
+    }
+
+    // 主版本与次版本相同
+    pub fn same_minor(&self, other: &Self) -> bool {
+        self.v1 == other.v1 && self.v2 == other.v2
+    }
+}
+
+impl Display for Release {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "go{}", self.v1)?;
+
+        for x in [&self.v2, &self.v3].into_iter().flatten() {
+            write!(f, ".{}", *x)?;
         }
+
+        if let Some(ref x) = self.unstable_v4 {
+            write!(f, "{x}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Ord for Release {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.v1, self.v2, self.v3)
+            .cmp(&(other.v1, other.v2, other.v3))
+            .then_with(|| match (&self.unstable_v4, &other.unstable_v4) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(x), Some(y)) => x.cmp(y),
+            })
     }
 }
 
+impl PartialOrd for Release {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// 按版本号比较安装目录名，go1.9小于go1.21，无法识别版本号的开发版本等小于所有发布版本，
+// 版本号相同时按名称比较，如go1.22.3与go1.22.3+ourfix
+pub fn cmp_version_name(x: &str, y: &str) -> Ordering {
+    Release::parse(x)
+        .cmp(&Release::parse(y))
+        .then_with(|| x.cmp(y))
+}
+
+// 数据库同时只能被一个进程打开，其他进程等待，下载、编译等耗时操作前通过release释放
 pub struct Db {
+    path: PathBuf,
+    open: Mutex<Option<OpenDb>>,
+}
+
+// 打开的数据库与其锁，数据库先于锁关闭
+struct OpenDb {
     db: Database,
+    _lock: FileLock,
 }
 
 impl Db {
     const VERSION_TREE: &'static str = "version";
+    const ALIAS_TREE: &'static str = "alias";
+    const INSTALLED_TREE: &'static str = "installed";
     const META_OS: &'static str = "meta_os";
     const META_ARCH: &'static str = "meta_arch";
     const META_VERSIONS: &'static str = "meta_versions";
     const PROGRAM_STATE: &'static str = "program_state";
+    const SDK_LINKS: &'static str = "sdk_links";
 
     pub fn new(path: impl AsRef<Path>) -> Result<Self> {
-        let db = sled::open(path)?;
+        let db = Self {
+            path: path.as_ref().to_path_buf(),
+            open: Mutex::new(None),
+        };
+        db.db()?;
+
+        Ok(db)
+    }
 
-        Ok(Self { db })
+    // 已释放时重新获取锁并打开
+    fn db(&self) -> Result<Database> {
+        let mut open = self.open.lock().unwrap();
+        if let Some(x) = open.as_ref() {
+            return Ok(x.db.clone());
+        }
+
+        let lock = FileLock::acquire(lock_path(&self.path)?)?;
+        let db = sled::open(&self.path)?;
+        *open = Some(OpenDb {
+            db: db.clone(),
+            _lock: lock,
+        });
+
+        Ok(db)
+    }
+
+    // 关闭数据库并释放锁，其他进程可以在此期间访问，之后的访问会重新打开
+    pub fn release(&self) {
+        self.open.lock().unwrap().take();
     }
 
     pub fn program_state(&self) -> Result<ProgramState> {
-        Ok(self.db.load(Self::PROGRAM_STATE)?.unwrap_or_default())
+        Ok(self.db()?.load(Self::PROGRAM_STATE)?.unwrap_or_default())
     }
 
     pub fn store_program_state(&self, data: &ProgramState) -> Result<()> {
-        self.db.store(Self::PROGRAM_STATE, data)?;
+        self.db()?.store(Self::PROGRAM_STATE, data)?;
+
+        Ok(())
+    }
+
+    // 是否在~/sdk中维护指向安装目录的链接
+    pub fn sdk_links(&self) -> Result<bool> {
+        Ok(self.db()?.load(Self::SDK_LINKS)?.unwrap_or_default())
+    }
+
+    pub fn set_sdk_links(&self, enabled: bool) -> Result<()> {
+        self.db()?.store(Self::SDK_LINKS, &enabled)?;
 
         Ok(())
     }
@@ -197,8 +416,8 @@ impl Db {
 
         let (os, arch, versions) = Self::calculate_meta(&vers);
 
-        self.db.drop_tree(Self::VERSION_TREE)?;
-        let tree = self.db.open_tree(Self::VERSION_TREE)?;
+        self.db()?.drop_tree(Self::VERSION_TREE)?;
+        let tree = self.db()?.open_tree(Self::VERSION_TREE)?;
 
         tree.store(Self::META_OS, &os)?;
         tree.store(Self::META_ARCH, &arch)?;
@@ -213,7 +432,7 @@ impl Db {
 
     pub fn os(&self) -> Result<Vec<String>> {
         let os: HashSet<String> = self
-            .db
+            .db()?
             .open_tree(Self::VERSION_TREE)?
             .load(Self::META_OS)?
             .unwrap_or_default();
@@ -226,7 +445,7 @@ impl Db {
 
     pub fn arch(&self) -> Result<Vec<String>> {
         let arch: HashSet<String> = self
-            .db
+            .db()?
             .open_tree(Self::VERSION_TREE)?
             .load(Self::META_ARCH)?
             .unwrap_or_default();
@@ -238,13 +457,29 @@ impl Db {
     }
 
     pub fn version(&self, ver: &str) -> Result<Option<Version>> {
-        let version: Option<Version> = self.db.open_tree(Self::VERSION_TREE)?.load(ver)?;
+        let version: Option<Version> = self.db()?.open_tree(Self::VERSION_TREE)?.load(ver)?;
 
         Ok(version)
     }
 
+    // 目录中的安装包，不包括只能通过--from-source编译安装的源码包
     pub fn versions(&self, os: Option<&str>, arch: Option<&str>) -> Result<Vec<Version>> {
-        let tree = self.db.open_tree(Self::VERSION_TREE)?;
+        let mut v = self.load_versions(os, arch)?;
+        v.retain(|x| !x.is_source());
+
+        Ok(v)
+    }
+
+    // 目录中的源码包
+    pub fn source_versions(&self) -> Result<Vec<Version>> {
+        let mut v = self.load_versions(Some(SOURCE_OS), None)?;
+        v.retain(|x| x.is_source());
+
+        Ok(v)
+    }
+
+    fn load_versions(&self, os: Option<&str>, arch: Option<&str>) -> Result<Vec<Version>> {
+        let tree = self.db()?.open_tree(Self::VERSION_TREE)?;
         let mut v: Vec<Version> = tree
             .iter()
             // 获取数据库所有的键
@@ -279,17 +514,84 @@ impl Db {
         Ok(v)
     }
 
+    pub fn alias(&self, name: &str) -> Result<Option<String>> {
+        Ok(self.db()?.open_tree(Self::ALIAS_TREE)?.load(name)?)
+    }
+
+    pub fn set_alias(&self, name: &str, version: &str) -> Result<()> {
+        self.db()?
+            .open_tree(Self::ALIAS_TREE)?
+            .store(name, &version.to_string())?;
+
+        Ok(())
+    }
+
+    // 删除别名，别名不存在时返回false
+    pub fn remove_alias(&self, name: &str) -> Result<bool> {
+        Ok(self
+            .db()?
+            .open_tree(Self::ALIAS_TREE)?
+            .remove(name)?
+            .is_some())
+    }
+
+    pub fn aliases(&self) -> Result<Vec<(String, String)>> {
+        let tree = self.db()?.open_tree(Self::ALIAS_TREE)?;
+
+        Ok(tree
+            .iter()
+            .keys()
+            .flatten()
+            .flat_map(|x| String::from_utf8(x.to_vec()))
+            .flat_map(|x| Some((x.clone(), tree.load(&x).ok()??)))
+            .collect())
+    }
+
+    pub fn installation(&self, version: &str) -> Result<Option<Installation>> {
+        Ok(self.db()?.open_tree(Self::INSTALLED_TREE)?.load(version)?)
+    }
+
+    pub fn installations(&self) -> Result<Vec<Installation>> {
+        let tree = self.db()?.open_tree(Self::INSTALLED_TREE)?;
+        let mut v: Vec<Installation> = tree
+            .iter()
+            .values()
+            .flatten()
+            .flat_map(|x| bincode::deserialize(x.as_ref()))
+            .collect();
+
+        v.sort_by(|x, y| cmp_version_name(&y.version, &x.version));
+
+        Ok(v)
+    }
+
+    pub fn store_installation(&self, x: &Installation) -> Result<()> {
+        self.db()?
+            .open_tree(Self::INSTALLED_TREE)?
+            .store(&x.version, x)?;
+
+        Ok(())
+    }
+
+    pub fn remove_installation(&self, version: &str) -> Result<()> {
+        self.db()?
+            .open_tree(Self::INSTALLED_TREE)?
+            .remove(version)?;
+
+        Ok(())
+    }
+
     fn calculate_meta(vers: &[Version]) -> (HashSet<String>, HashSet<String>, Vec<String>) {
         let mut os = HashSet::new();
         let mut arch = HashSet::new();
         let mut short_version = Vec::<String>::new();
 
         vers.iter().for_each(|x| {
-            if !os.contains(&x.os) {
+            if !x.is_source() && !os.contains(&x.os) {
                 os.insert(x.os.clone());
             }
 
-            if !arch.contains(&x.arch) {
+            if !x.arch.is_empty() && !arch.contains(&x.arch) {
                 arch.insert(x.arch.clone());
             }
 
@@ -313,7 +615,9 @@ impl Db {
 
 #[cfg(test)]
 mod tests {
-    use crate::data::{Compress, UnstableVersion, Version};
+    use crate::data::{
+        cmp_version_name, Compress, Installation, Release, UnstableVersion, Version,
+    };
 
     #[test]
     fn ord() {
@@ -349,8 +653,73 @@ mod tests {
             compress: Compress::TarGz,
         };
 
-        assert_eq!(v1 > v2, true);
-        assert_eq!(v2 < v1, true);
-        assert_eq!(v2 > v1, false);
+        assert!(v1 > v2);
+        assert!(v2 < v1);
+        assert!(v2 <= v1);
+        assert!(r1 < r2 && b1 < b2 && b2 < r1);
+    }
+
+    #[test]
+    fn release() {
+        let x = Release::parse("go1.21.3.linux-amd64").unwrap();
+        assert_eq!(x.to_string(), "go1.21.3");
+
+        let rc = Release::parse("1.22rc1").unwrap();
+        assert_eq!(rc.unstable_v4, Some(UnstableVersion::RC(1)));
+        assert!(rc < Release::parse("1.22.0").unwrap());
+        assert!(Release::parse("go1.21").unwrap() < Release::parse("go1.21.0").unwrap());
+        assert_eq!(
+            Release::parse("go1.22.3+ourfix"),
+            Release::parse("go1.22.3")
+        );
+        assert!(Release::parse("linux").is_none());
+    }
+
+    #[test]
+    fn legacy_name() {
+        assert_eq!(
+            Installation::legacy_name("go1.21.3.amd64-linux").as_deref(),
+            Some("go1.21.3.linux-amd64")
+        );
+        assert_eq!(
+            Installation::legacy_name("go1.22rc1.armv6l-linux").as_deref(),
+            Some("go1.22rc1.linux-armv6l")
+        );
+        assert_eq!(Installation::legacy_name("go1.21.3.linux-amd64"), None);
+        assert_eq!(Installation::legacy_name("go1.22.3+ourfix"), None);
+        assert_eq!(Installation::legacy_name("go1.21.3.src"), None);
+        assert_eq!(Installation::legacy_name("my.build-linux"), None);
+        assert_eq!(Installation::legacy_name("1.21.3.amd64-linux"), None);
+        assert_eq!(Installation::legacy_name("go1.21.3+fix.amd64-linux"), None);
+        assert_eq!(
+            Installation::platform_of("go1.21.3.amd64-linux"),
+            Some((String::from("linux"), String::from("amd64")))
+        );
+    }
+
+    #[test]
+    fn version_name() {
+        let mut names = vec![
+            "go1.21.3",
+            "go1.9",
+            "devel-go1.24-abc1234",
+            "go1.22.3+ourfix",
+            "go1.22rc1",
+            "go1.22.3",
+            "go1.9.2",
+        ];
+        names.sort_by(|x, y| cmp_version_name(y, x));
+        assert_eq!(
+            names,
+            [
+                "go1.22.3+ourfix",
+                "go1.22.3",
+                "go1.22rc1",
+                "go1.21.3",
+                "go1.9.2",
+                "go1.9",
+                "devel-go1.24-abc1234",
+            ]
+        );
     }
 }
@@ -30,6 +30,16 @@ pub enum Reason {
     InvalidInstallPath,
     #[error("无效的下载路径")]
     InvalidDownloadPath,
+    #[error("未安装的go版本")]
+    VersionNotInstalled,
+    #[error("go版本已安装")]
+    VersionInstalled,
+    #[error("无效的GOROOT")]
+    InvalidGoRoot,
+    #[error("磁盘空间不足")]
+    InsufficientDiskSpace,
+    #[error("编译失败")]
+    BuildFailed,
 }
 
 macro_rules! impl_from_error {
@@ -50,3 +60,4 @@ impl_from_error!(ureq::Error, Reason::ConnectionFailed);
 impl_from_error!(serde_xml_rs::Error, Reason::InvalidXml);
 impl_from_error!(sled::Error, Reason::OpenDatabaseFailed);
 impl_from_error!(compress_tools::Error, Reason::UncompressFailed);
+impl_from_error!(serde_json::Error, Reason::InvalidResource);
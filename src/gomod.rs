@@ -0,0 +1,137 @@
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use crate::data::Release;
+use crate::errors::Result;
+
+const GO_WORK: &str = "go.work";
+const GO_MOD: &str = "go.mod";
+
+// go.mod或go.work中对go版本的要求
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    pub path: PathBuf,
+    // go指令，要求的最低版本
+    pub go: Option<Release>,
+    // toolchain指令，建议使用的版本
+    pub toolchain: Option<Release>,
+}
+
+impl Requirement {
+    // 查找dir所在项目的要求，go.work优先于go.mod，与go命令的行为一致
+    pub fn find(dir: &Path) -> Result<Option<Self>> {
+        for name in [GO_WORK, GO_MOD] {
+            for x in dir.ancestors() {
+                let path = x.join(name);
+                let text = match fs::read_to_string(&path) {
+                    Ok(x) => x,
+                    Err(e) if matches!(e.kind(), io::ErrorKind::NotFound) => continue,
+                    Err(e) => return Err(e.into()),
+                };
+
+                let x = Self::parse(path, &text);
+                if x.go.is_some() || x.toolchain.is_some() {
+                    return Ok(Some(x));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn parse(path: PathBuf, text: &str) -> Self {
+        let mut go = None;
+        let mut toolchain = None;
+
+        for line in text.lines() {
+            let line = match line.find("//") {
+                Some(i) => &line[..i],
+                None => line,
+            };
+
+            let mut fields = line.split_whitespace();
+            match (fields.next(), fields.next()) {
+                (Some("go"), Some(x)) => go = Release::parse(x),
+                // toolchain default表示不指定
+                (Some("toolchain"), Some(x)) if x.starts_with("go") => {
+                    toolchain = Release::parse(x)
+                }
+                _ => {}
+            }
+        }
+
+        Self {
+            path,
+            go,
+            toolchain,
+        }
+    }
+
+    // 最希望使用的版本，toolchain不低于go版本时使用toolchain
+    pub fn preferred(&self) -> Option<&Release> {
+        match (&self.go, &self.toolchain) {
+            (Some(go), Some(toolchain)) if toolchain < go => Some(go),
+            (go, None) => go.as_ref(),
+            (_, toolchain) => toolchain.as_ref(),
+        }
+    }
+
+    pub fn satisfied_by(&self, r: &Release) -> bool {
+        self.preferred().map(|x| r >= x).unwrap_or(true)
+    }
+
+    // 从候选版本中选出最合适的，依次为完全一致、同一次版本中最新、满足要求中最新
+    pub fn best<'a, T>(
+        &self,
+        candidates: &'a [T],
+        release: impl Fn(&T) -> Release,
+    ) -> Option<&'a T> {
+        let preferred = self.preferred()?;
+        let mut matched: Vec<(Release, &T)> = candidates
+            .iter()
+            .map(|x| (release(x), x))
+            .filter(|x| self.satisfied_by(&x.0))
+            .collect();
+        matched.sort_by(|x, y| y.0.cmp(&x.0));
+
+        matched
+            .iter()
+            .find(|x| &x.0 == preferred)
+            .or_else(|| matched.iter().find(|x| x.0.same_minor(preferred)))
+            .or_else(|| matched.first())
+            .map(|x| x.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::Release;
+    use crate::gomod::Requirement;
+    use std::path::PathBuf;
+
+    #[test]
+    fn parse() {
+        let text = "module example.com/x\n\ngo 1.22.1 // minimum\n\ntoolchain go1.22.3\n";
+        let x = Requirement::parse(PathBuf::from("go.mod"), text);
+        assert_eq!(x.go, Release::parse("1.22.1"));
+        assert_eq!(x.preferred(), Release::parse("go1.22.3").as_ref());
+
+        let x = Requirement::parse(PathBuf::from("go.mod"), "go 1.21\ntoolchain default\n");
+        assert_eq!(x.toolchain, None);
+        assert_eq!(x.preferred(), Release::parse("1.21").as_ref());
+    }
+
+    #[test]
+    fn best() {
+        let x = Requirement::parse(PathBuf::from("go.mod"), "go 1.21.1\n");
+        let installed = ["go1.20.7", "go1.21.0", "go1.21.5", "go1.22.0"];
+        let release = |x: &&str| Release::parse(x).unwrap();
+
+        assert_eq!(x.best(&installed, release), Some(&"go1.21.5"));
+        assert_eq!(x.best(&installed[..2], release), None);
+        assert_eq!(
+            x.best(&["go1.22.0", "go1.23.1"], release),
+            Some(&"go1.23.1")
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::common::{host_arch, host_os};
+use crate::data::Release;
+use crate::errors::{Error, Reason, Result};
+
+// 一个GOROOT中必须存在的内容，bin/go在windows上还有.exe后缀
+const LAYOUT: &[&str] = &["bin/go", "VERSION", "src", "pkg/tool"];
+
+// GOROOT的版本信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoRootInfo {
+    // VERSION文件中的版本，如go1.21.3
+    pub version: String,
+    pub os: String,
+    pub arch: String,
+}
+
+impl GoRootInfo {
+    pub fn release(&self) -> Option<Release> {
+        Release::parse(&self.version)
+    }
+}
+
+// 检查当前平台的目录结构
+pub fn check_layout(goroot: &Path) -> Result<()> {
+    check_platform_layout(goroot, host_os())
+}
+
+// 检查os平台的目录结构
+pub fn check_platform_layout(goroot: &Path, os: &str) -> Result<()> {
+    for x in LAYOUT {
+        let x = match (*x, os) {
+            ("bin/go", "windows") => "bin/go.exe",
+            (x, _) => x,
+        };
+        if !goroot.join(x).exists() {
+            return Err(Error {
+                kind: Reason::InvalidGoRoot,
+                msg: format!("{}中缺少{x}", goroot.display()),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+// VERSION文件的第一行
+pub fn read_version(goroot: &Path) -> Result<String> {
+    let text = fs::read_to_string(goroot.join("VERSION"))?;
+
+    text.lines()
+        .next()
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+        .ok_or_else(|| Error {
+            kind: Reason::InvalidGoRoot,
+            msg: format!("{}中的VERSION文件为空", goroot.display()),
+        })
+}
+
+// 执行go version，返回报告的版本与平台
+pub fn go_version(goroot: &Path) -> Result<(String, String, String)> {
+    let output = Command::new(goroot.join("bin").join("go"))
+        .arg("version")
+        .current_dir(goroot)
+        .env("GOROOT", goroot)
+        // 避免根据环境中的go.mod切换到其他版本
+        .env("GOTOOLCHAIN", "local")
+        .output()?;
+    let text = String::from_utf8_lossy(&output.stdout).to_string();
+
+    // go version go1.21.3 linux/amd64
+    let fields: Vec<_> = text.split_whitespace().collect();
+    let platform = fields.last().and_then(|x| x.split_once('/'));
+    match (output.status.success(), fields.get(2), platform) {
+        (true, Some(version), Some((os, arch))) => {
+            Ok((version.to_string(), os.to_string(), arch.to_string()))
+        }
+        _ => Err(Error {
+            kind: Reason::InvalidGoRoot,
+            msg: format!("无法识别go version的输出: {}", text.trim()),
+        }),
+    }
+}
+
+// 检查目录结构并读取版本信息，要求VERSION文件与go version的结果一致
+pub fn inspect(goroot: &Path) -> Result<GoRootInfo> {
+    check_layout(goroot)?;
+
+    let version = read_version(goroot)?;
+    let (reported, os, arch) = go_version(goroot)?;
+    if reported != version {
+        return Err(Error {
+            kind: Reason::InvalidGoRoot,
+            msg: format!("VERSION文件为{version}，go version报告{reported}"),
+        });
+    }
+
+    Ok(GoRootInfo { version, os, arch })
+}
+
+// 检查本机编译的开发版本，没有VERSION文件，go version报告为devel
+pub fn validate_devel(goroot: &Path) -> Result<()> {
+    let (version, os, arch) = go_version(goroot)?;
+    if version != "devel" {
+        return Err(Error {
+            kind: Reason::InvalidGoRoot,
+            msg: format!("期望的版本为开发版本，实际为{version}"),
+        });
+    }
+
+    if os != host_os() || arch != host_arch() {
+        return Err(Error {
+            kind: Reason::InvalidGoRoot,
+            msg: format!(
+                "期望的平台为{}/{}，实际为{os}/{arch}",
+                host_os(),
+                host_arch()
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+// 检查GOROOT是否为期望的版本与平台，其他平台的go无法执行，只检查目录结构与VERSION文件
+pub fn validate(goroot: &Path, release: &Release, os: &str, arch: &str) -> Result<GoRootInfo> {
+    let info = if os == host_os() && arch == host_arch() {
+        inspect(goroot)?
+    } else {
+        check_platform_layout(goroot, os)?;
+
+        GoRootInfo {
+            version: read_version(goroot)?,
+            os: os.to_string(),
+            arch: goarch(arch).to_string(),
+        }
+    };
+
+    if info.release().as_ref() != Some(release) {
+        return Err(Error {
+            kind: Reason::InvalidGoRoot,
+            msg: format!("期望的版本为{release}，实际为{}", info.version),
+        });
+    }
+
+    if info.os != os || info.arch != goarch(arch) {
+        return Err(Error {
+            kind: Reason::InvalidGoRoot,
+            msg: format!("期望的平台为{os}/{arch}，实际为{}/{}", info.os, info.arch),
+        });
+    }
+
+    Ok(info)
+}
+
+// 安装包名称中的arch与GOARCH不完全一致
+fn goarch(arch: &str) -> &str {
+    match arch {
+        "armv6l" => "arm",
+        x => x,
+    }
+}
+
+// GOARCH对应的安装包名称中的arch
+pub fn package_arch(goarch: &str) -> &str {
+    match goarch {
+        "arm" => "armv6l",
+        x => x,
+    }
+}
+
+// 常见位置中已有的GOROOT，包括官方安装包、发行版及golang.org/dl的安装
+pub fn known_locations() -> Vec<PathBuf> {
+    let mut paths = vec![
+        PathBuf::from("/usr/local/go"),
+        PathBuf::from("/usr/lib/go"),
+        PathBuf::from("/usr/lib/golang"),
+    ];
+
+    let mut roots = vec![(PathBuf::from("/usr/lib"), "go-")];
+    if let Some(home) = dirs::home_dir() {
+        roots.push((home.join("sdk"), "go"));
+    }
+    for (dir, prefix) in roots {
+        let mut found: Vec<_> = fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|x| x.file_name().to_string_lossy().starts_with(prefix))
+            .map(|x| x.path())
+            .collect();
+        found.sort();
+        paths.extend(found);
+    }
+
+    // 发行版中的/usr/lib/go通常是指向具体版本的链接
+    let mut seen = Vec::new();
+    paths
+        .into_iter()
+        .filter(|x| check_layout(x).is_ok())
+        .filter(|x| match x.canonicalize() {
+            Ok(x) if !seen.contains(&x) => {
+                seen.push(x);
+                true
+            }
+            _ => false,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{host_arch, host_os};
+    use crate::data::Release;
+    use crate::goroot::validate;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn foreign_platform() {
+        let goroot = std::env::temp_dir().join(format!("pgvm_goroot_{}", process::id()));
+        fs::create_dir_all(goroot.join("bin")).unwrap();
+        fs::create_dir_all(goroot.join("src")).unwrap();
+        fs::create_dir_all(goroot.join("pkg").join("tool")).unwrap();
+        fs::write(goroot.join("VERSION"), "go1.21.3\ntime 2023-10-09\n").unwrap();
+        // 无法执行的go.exe
+        fs::write(goroot.join("bin").join("go.exe"), "MZ").unwrap();
+
+        let release = Release::parse("1.21.3").unwrap();
+        let info = validate(&goroot, &release, "windows", "amd64").unwrap();
+        assert_eq!((info.os.as_str(), info.arch.as_str()), ("windows", "amd64"));
+        assert!(validate(
+            &goroot,
+            &Release::parse("1.21.4").unwrap(),
+            "windows",
+            "amd64"
+        )
+        .is_err());
+        // 当前平台需要bin/go且可以执行
+        assert!(validate(&goroot, &release, host_os(), host_arch()).is_err());
+
+        fs::remove_dir_all(&goroot).unwrap();
+    }
+}
@@ -1,9 +1,14 @@
-use compress_tools::{uncompress_archive, Ownership};
+use compress_tools::{uncompress_archive, uncompress_archive_file, Ownership};
+use flate2::read::GzDecoder;
+use openssl::sha::Sha256;
+use pgvm::common::WriteSha256;
 use pgvm::errors::{Error, Reason, Result};
+use tar::Archive;
 
-use std::fs::rename;
-use std::io::{Read, Seek};
-use std::path::Path;
+use std::fs::{self, rename, File};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 
 // 插入一行到文件中，如果该行不存在
 // fn exits_line_or_install<P: AsRef<Path>>(p: P, line: &str) -> Result<()> {
@@ -29,21 +34,458 @@ use std::path::Path;
 //     Ok(())
 // }
 
-pub fn install<R: Read + Seek, D: AsRef<Path>>(r: &mut R, dest: D) -> Result<()> {
-    let path = if let Some(name) = dest.as_ref().file_name() {
-        dest.as_ref()
-            .with_file_name(&format!("{}.bak", name.to_str().unwrap()))
-    } else {
-        return Err(Error {
-            kind: Reason::InvalidInstallPath,
-            msg: String::from("无效的安装路径"),
+// 安装过程中使用的临时目录，drop时删除其中剩余的内容
+pub struct Staging {
+    path: PathBuf,
+}
+
+impl Staging {
+    // 在dest同级创建临时目录，保证与安装目录位于同一文件系统，
+    // 调用者需要持有dest的安装锁，此时同级的其他临时目录都是被中断的安装留下的，一并删除
+    pub fn new<D: AsRef<Path>>(dest: D) -> Result<Self> {
+        let name = match dest.as_ref().file_name().and_then(|x| x.to_str()) {
+            Some(x) => x,
+            None => {
+                return Err(Error {
+                    kind: Reason::InvalidInstallPath,
+                    msg: String::from("无效的安装路径"),
+                })
+            }
+        };
+
+        let prefix = format!(".{}.tmp-", name);
+        if let Some(parent) = dest.as_ref().parent() {
+            match fs::read_dir(parent) {
+                Ok(dir) => {
+                    for x in dir.flatten() {
+                        if x.file_name().to_string_lossy().starts_with(&prefix) {
+                            fs::remove_dir_all(x.path())?;
+                        }
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let path = dest
+            .as_ref()
+            .with_file_name(format!("{}{}", prefix, process::id()));
+        fs::create_dir_all(&path)?;
+
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // 将临时目录移动到dest，force时原子地替换已存在的安装
+    pub fn commit<D: AsRef<Path>>(self, dest: D, force: bool) -> Result<()> {
+        let dest = dest.as_ref();
+
+        sync_tree(&self.path)?;
+
+        if dest.symlink_metadata().is_err() {
+            rename(&self.path, dest)?;
+        } else if force {
+            // 交换后临时目录中是旧的安装，由drop删除
+            exchange(&self.path, dest)?;
+        } else {
+            return Err(Error {
+                kind: Reason::VersionInstalled,
+                msg: format!("{}已存在", dest.display()),
+            });
+        }
+
+        if let Some(parent) = dest.parent() {
+            File::open(parent)?.sync_all()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        if self.path.exists() {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+// 解压到临时目录，通过validate检查后再替换到dest，失败时不会留下任何内容
+pub fn install<R, D, V>(r: &mut R, dest: D, force: bool, validate: V) -> Result<()>
+where
+    R: Read + Seek,
+    D: AsRef<Path>,
+    V: FnOnce(&Path) -> Result<()>,
+{
+    let staging = Staging::new(&dest)?;
+
+    // 解压
+    uncompress_archive(r, staging.path(), Ownership::Preserve)?;
+    // 检查解压的内容
+    validate(staging.path())?;
+    // 替换到指定位置
+    staging.commit(dest, force)
+}
+
+// 边读取边解压tar.gz，同时计算sha256并将读到的内容写入w，hash与expected一致时才替换到dest
+pub fn install_stream<R, W, D, V>(
+    r: R,
+    w: W,
+    dest: D,
+    force: bool,
+    expected: Option<&str>,
+    validate: V,
+) -> Result<String>
+where
+    R: Read,
+    W: Write,
+    D: AsRef<Path>,
+    V: FnOnce(&Path) -> Result<()>,
+{
+    let staging = Staging::new(&dest)?;
+
+    let mut tee = Tee::new(r, w);
+    let mut archive = Archive::new(ReadCheck::new(GzDecoder::new(&mut tee)));
+    archive.set_preserve_permissions(true);
+    archive.set_preserve_mtime(true);
+    let unpacked = archive.unpack(staging.path());
+    let read_failed = archive.into_inner().failed;
+    // 读取或解码安装包出错时视为安装包损坏，写入安装目录或缓存的错误保持原样
+    if let Err(e) = unpacked {
+        return Err(match read_failed && !tee.write_failed {
+            true => corrupt(e),
+            false => e.into(),
         });
+    }
+
+    // tar结束标记之后可能还有内容，读完才能得到整个文件的hash
+    let (sha256, mut w) = tee
+        .finish()
+        .map_err(|(e, write_failed)| match write_failed {
+            true => Error::from(e),
+            false => corrupt(e),
+        })?;
+    w.flush()?;
+    if let Some(expected) = expected {
+        if sha256 != expected.to_lowercase() {
+            return Err(Error {
+                kind: Reason::Hashinconformity,
+                msg: String::from("sha256 hash不一致"),
+            });
+        }
+    }
+
+    validate(staging.path())?;
+    staging.commit(dest, force)?;
+
+    Ok(sha256)
+}
+
+// 解压pack生成的tar.zst打包文件，通过validate检查后再替换到dest
+pub fn install_bundle<R, D, V>(r: R, dest: D, force: bool, validate: V) -> Result<()>
+where
+    R: Read,
+    D: AsRef<Path>,
+    V: FnOnce(&Path) -> Result<()>,
+{
+    let staging = Staging::new(&dest)?;
+
+    let mut archive = Archive::new(zstd::Decoder::new(r)?);
+    archive.set_preserve_permissions(true);
+    archive.set_preserve_mtime(true);
+    archive.unpack(staging.path())?;
+
+    validate(staging.path())?;
+    staging.commit(dest, force)
+}
+
+// 安装包无法解压或hash不一致，编译、检查与替换安装目录的错误不属于此类
+pub fn is_corrupt(e: &Error) -> bool {
+    matches!(e.kind, Reason::UncompressFailed | Reason::Hashinconformity)
+}
+
+fn corrupt(e: io::Error) -> Error {
+    Error {
+        kind: Reason::UncompressFailed,
+        msg: e.to_string(),
+    }
+}
+
+// 读取安装包中go/VERSION文件的第一行，如go1.21.3
+pub fn archive_version<R: Read + Seek>(r: &mut R) -> Result<String> {
+    let mut buf = Vec::new();
+    uncompress_archive_file(r, &mut buf, "go/VERSION")?;
+
+    String::from_utf8_lossy(&buf)
+        .lines()
+        .next()
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+        .ok_or_else(|| Error {
+            kind: Reason::InvalidResource,
+            msg: String::from("安装包中的VERSION文件为空"),
+        })
+}
+
+// 读取的同时计算sha256，并将内容写入w，write_failed记录错误是否来自写入w
+struct Tee<R, W> {
+    inner: R,
+    w: W,
+    hasher: WriteSha256,
+    write_failed: bool,
+}
+
+impl<R: Read, W: Write> Tee<R, W> {
+    fn new(inner: R, w: W) -> Self {
+        Self {
+            inner,
+            w,
+            hasher: WriteSha256::new(Sha256::new()),
+            write_failed: false,
+        }
+    }
+
+    // 读完剩余的内容，返回hash，失败时同时返回错误是否来自写入w
+    fn finish(mut self) -> std::result::Result<(String, W), (io::Error, bool)> {
+        if let Err(e) = io::copy(&mut self, &mut io::sink()) {
+            return Err((e, self.write_failed));
+        }
+
+        Ok((hex::encode(self.hasher.into_sha256().finish()), self.w))
+    }
+}
+
+impl<R: Read, W: Write> Read for Tee<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.write_all(&buf[..n])?;
+        if let Err(e) = self.w.write_all(&buf[..n]) {
+            self.write_failed = true;
+            return Err(e);
+        }
+
+        Ok(n)
+    }
+}
+
+// 记录读取是否出错，用于区分安装包损坏与写入安装目录失败
+struct ReadCheck<R> {
+    inner: R,
+    failed: bool,
+}
+
+impl<R> ReadCheck<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            failed: false,
+        }
+    }
+}
+
+impl<R: Read> Read for ReadCheck<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let r = self.inner.read(buf);
+        self.failed |= r.is_err();
+
+        r
+    }
+}
+
+// 复制目录，保留符号链接与权限，跳过名称在skip中的内容
+pub fn copy_tree(src: &Path, dst: &Path, skip: &[&str]) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+    fs::set_permissions(dst, fs::metadata(src)?.permissions())?;
+
+    for x in fs::read_dir(src)? {
+        let x = x?;
+        if skip.iter().any(|s| x.file_name() == *s) {
+            continue;
+        }
+
+        let target = dst.join(x.file_name());
+        let meta = x.file_type()?;
+        if meta.is_dir() {
+            copy_tree(&x.path(), &target, skip)?;
+        } else if meta.is_symlink() {
+            std::os::unix::fs::symlink(fs::read_link(x.path())?, &target)?;
+        } else {
+            fs::copy(x.path(), &target)?;
+        }
+    }
+
+    Ok(())
+}
+
+// 将目录移动到to，如GOROOT，to为链接时替换，不同文件系统之间先复制再删除原目录
+pub fn move_tree(from: &Path, to: &Path) -> Result<()> {
+    let tmp = to.with_file_name(format!(".go.move-{}", process::id()));
+    let copied = match fs::rename(from, &tmp) {
+        Ok(_) => false,
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+            if let Err(e) = copy_tree(from, &tmp, &[]) {
+                let _ = fs::remove_dir_all(&tmp);
+                return Err(e.into());
+            }
+
+            true
+        }
+        Err(e) => return Err(e.into()),
     };
 
-    // 解压
-    uncompress_archive(r, &path, Ownership::Preserve)?;
-    // 重命到指定位置
-    rename(&path, dest)?;
+    let r = match to.symlink_metadata() {
+        Ok(x) if x.file_type().is_symlink() => fs::remove_file(to),
+        _ => Ok(()),
+    }
+    .and_then(|_| rename(&tmp, to));
+    if let Err(e) = r {
+        // 恢复原目录
+        if copied {
+            let _ = fs::remove_dir_all(&tmp);
+        } else {
+            let _ = rename(&tmp, from);
+        }
+
+        return Err(e.into());
+    }
+
+    if copied {
+        if let Err(e) = fs::remove_dir_all(from) {
+            eprintln!("删除{}失败: {}", from.display(), e);
+        }
+    }
 
     Ok(())
 }
+
+// 将目录中的所有内容写入磁盘
+fn sync_tree(path: &Path) -> io::Result<()> {
+    let meta = fs::symlink_metadata(path)?;
+    if meta.is_dir() {
+        for x in fs::read_dir(path)? {
+            sync_tree(&x?.path())?;
+        }
+    } else if !meta.is_file() {
+        return Ok(());
+    }
+
+    File::open(path)?.sync_all()
+}
+
+// 原子地交换两个路径
+#[cfg(target_os = "linux")]
+fn exchange(a: &Path, b: &Path) -> Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let a = CString::new(a.as_os_str().as_bytes()).unwrap();
+    let b = CString::new(b.as_os_str().as_bytes()).unwrap();
+
+    let r = unsafe {
+        libc::syscall(
+            libc::SYS_renameat2,
+            libc::AT_FDCWD,
+            a.as_ptr(),
+            libc::AT_FDCWD,
+            b.as_ptr(),
+            libc::RENAME_EXCHANGE,
+        )
+    };
+    if r != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    Ok(())
+}
+
+// 不支持原子交换的系统上先移走旧的安装，中间会短暂缺失
+#[cfg(not(target_os = "linux"))]
+fn exchange(a: &Path, b: &Path) -> Result<()> {
+    let old = a.with_extension("old");
+    rename(b, &old)?;
+    rename(a, b)?;
+    rename(old, a)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::install::{install_stream, is_corrupt, Staging};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use pgvm::errors::{Error, Reason};
+    use std::fs;
+    use std::io;
+    use std::process;
+
+    #[test]
+    fn truncated() {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let data = vec![b'x'; 64 * 1024];
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "go/VERSION", data.as_slice())
+            .unwrap();
+        let archive = builder.into_inner().unwrap().finish().unwrap();
+
+        let dir = std::env::temp_dir().join(format!("pgvm_truncated_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let dest = dir.join("go1.21.3.linux-amd64");
+
+        let e = install_stream(
+            &archive[..archive.len() / 2],
+            io::sink(),
+            &dest,
+            false,
+            None,
+            |_| Ok(()),
+        )
+        .unwrap_err();
+        assert!(is_corrupt(&e));
+        assert!(fs::read_dir(&dir).unwrap().next().is_none());
+
+        // 检查失败不是安装包的问题，不应删除缓存
+        let e = install_stream(archive.as_slice(), io::sink(), &dest, false, None, |_| {
+            Err(Error {
+                kind: Reason::BuildFailed,
+                msg: String::from("make.bash"),
+            })
+        })
+        .unwrap_err();
+        assert!(!is_corrupt(&e));
+        assert!(fs::read_dir(&dir).unwrap().next().is_none());
+
+        install_stream(archive.as_slice(), io::sink(), &dest, false, None, |_| {
+            Ok(())
+        })
+        .unwrap();
+        assert!(dest.join("go").join("VERSION").is_file());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stale_staging() {
+        let dir = std::env::temp_dir().join(format!("pgvm_staging_{}", process::id()));
+        let stale = dir.join(".go1.21.3.linux-amd64.tmp-1");
+        let other = dir.join(".go1.21.4.linux-amd64.tmp-1");
+        fs::create_dir_all(stale.join("go")).unwrap();
+        fs::create_dir_all(&other).unwrap();
+
+        let staging = Staging::new(dir.join("go1.21.3.linux-amd64")).unwrap();
+        assert!(!stale.exists());
+        assert!(other.exists());
+        assert!(staging.path().is_dir());
+        drop(staging);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,5 +1,13 @@
+pub mod build;
+pub mod bundle;
 pub mod common;
 pub mod data;
 pub mod db;
 pub mod errors;
+pub mod gomod;
+pub mod goroot;
+pub mod lock;
 pub mod online;
+pub mod resolve;
+pub mod spec;
+pub mod xdg;
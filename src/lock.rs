@@ -0,0 +1,93 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use fs2::FileExt;
+
+use crate::errors::Result;
+
+// 进程间的建议锁，锁文件中记录持有者的pid，drop时释放
+pub struct FileLock {
+    file: File,
+}
+
+impl FileLock {
+    // 获取锁，被其他进程持有时提示并等待
+    pub fn acquire<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        if let Err(e) = file.try_lock_exclusive() {
+            if e.kind() != fs2::lock_contended_error().kind() {
+                return Err(e.into());
+            }
+
+            let mut pid = String::new();
+            file.read_to_string(&mut pid)?;
+            eprintln!("等待锁{}，当前由进程{}持有", path.display(), pid.trim());
+
+            file.lock_exclusive()?;
+        }
+
+        file.set_len(0)?;
+        file.rewind()?;
+        write!(file, "{}", process::id())?;
+        file.flush()?;
+
+        Ok(Self { file })
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = self.file.set_len(0);
+        let _ = self.file.unlock();
+    }
+}
+
+// 为path生成同级的锁文件路径，如a/b -> a/.b.lock
+pub fn lock_path<P: AsRef<Path>>(path: P) -> io::Result<PathBuf> {
+    let path = path.as_ref();
+    let name = path
+        .file_name()
+        .and_then(|x| x.to_str())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "无效的路径"))?;
+
+    Ok(path.with_file_name(format!(".{name}.lock")))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lock::{lock_path, FileLock};
+    use fs2::FileExt;
+    use std::fs::{self, File};
+    use std::process;
+
+    #[test]
+    fn exclusive() {
+        let path =
+            lock_path(std::env::temp_dir().join(format!("pgvm_lock_{}", process::id()))).unwrap();
+
+        let lock = FileLock::acquire(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            process::id().to_string()
+        );
+        assert!(File::open(&path).unwrap().try_lock_exclusive().is_err());
+
+        drop(lock);
+        assert!(File::open(&path).unwrap().try_lock_exclusive().is_ok());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,49 +1,293 @@
 pub(crate) mod cli;
 pub(crate) mod install;
+pub(crate) mod sdk;
+pub(crate) mod shell;
+pub(crate) mod shim;
+pub(crate) mod switch;
 
-use std::{fs, io};
+use std::{env, fs, io, process};
 
-use crate::cli::{Cli, Commands, Install, List, ShowMode};
+use crate::cli::{
+    Adopt, AdoptMode, Alias, AliasCommand, Cli, Commands, Exec, Install, Link, List, Pack, Sdk,
+    SdkCommand, Shim, ShowMode, Switch, Uninstall, Which, DEFAULT_DATABASE_PATH,
+    DEFAULT_DOWNLOAD_PATH,
+};
+use crate::shell::Activation;
 use clap::Parser;
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::Select;
-use indicatif::{HumanDuration, ProgressBar, ProgressState, ProgressStyle};
-use pgvm::data::{Db, Version};
+use indicatif::{HumanBytes, HumanDuration, ProgressBar, ProgressState, ProgressStyle};
+use openssl::sha::Sha256;
+use pgvm::bundle::{self, Manifest};
+use pgvm::common::{
+    ago, check_space, dir_size, host_arch, host_os, unix_now, writable, WriteSha256, BUILD_RATIO,
+    UNPACK_RATIO,
+};
+use pgvm::data::{
+    Compress, Db, InstallSource, Installation, Patch, Release, Version, KNOWN_OS, SOURCE_OS,
+};
 use pgvm::errors::{Error, Reason, Result};
-use pgvm::{errors, online};
+use pgvm::gomod::Requirement;
+use pgvm::lock::{lock_path, FileLock};
+use pgvm::resolve::Resolver;
+use pgvm::spec::VersionSpec;
+use pgvm::xdg::BaseDir;
+use pgvm::{build, errors, goroot, online, resolve};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
 use std::fs::{File, OpenOptions};
-use std::io::Write;
-use std::path::PathBuf;
-use std::time::{Duration, Instant};
+use std::io::{Seek, Write};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant, UNIX_EPOCH};
 use thiserror::private::PathAsDisplay;
 
 use pgvm::online::open_version;
 
+// 安装目录中存放各go版本的目录
+const VERSIONS_DIR: &str = "_pgvm_versions";
+
 struct Environment {
     database_path: PathBuf,
     download_path: PathBuf,
+    // 用户的安装位置
     install_path: PathBuf,
+    // 系统的安装位置，通常只读
+    system_path: PathBuf,
+    // 安装、卸载时使用系统的安装位置
+    system: bool,
+}
+
+impl Environment {
+    // 安装新版本的目录
+    fn versions_path(&self) -> PathBuf {
+        match self.system {
+            true => self.system_path.join(VERSIONS_DIR),
+            false => self.install_path.join(VERSIONS_DIR),
+        }
+    }
+
+    // 所有安装目录，用户的优先
+    fn versions_paths(&self) -> Vec<PathBuf> {
+        let mut paths = vec![self.install_path.join(VERSIONS_DIR)];
+        if self.system_path != self.install_path {
+            paths.push(self.system_path.join(VERSIONS_DIR));
+        }
+
+        paths
+    }
+
+    // 使用版本时所在的目录，两个安装目录中都有时使用用户的，都没有时为新安装的位置，
+    // 安装与卸载需要使用version_path_in指定安装目录
+    fn version_path(&self, version: &str) -> PathBuf {
+        self.resolver()
+            .path_of(version)
+            .unwrap_or_else(|| self.versions_path().join(version))
+    }
+
+    // 指向当前使用版本的链接
+    fn current_path(&self) -> PathBuf {
+        self.install_path.join("current")
+    }
+
+    // shim存放目录，需要加入PATH
+    fn shims_path(&self) -> PathBuf {
+        self.install_path.join("shims")
+    }
+
+    // go install安装程序的位置，各版本共用
+    fn gobin_path(&self) -> PathBuf {
+        self.install_path.join("bin")
+    }
+
+    // shim读取的别名文件，与数据库中的别名保持一致
+    fn aliases_path(&self) -> PathBuf {
+        self.install_path.join(resolve::ALIAS_FILE)
+    }
+
+    // golang.org/dl安装go的目录
+    fn sdk_path(&self) -> Option<PathBuf> {
+        dirs::home_dir().map(|x| x.join("sdk"))
+    }
+
+    // 安装目录root中的版本目录
+    fn version_path_in(root: &Path, version: &str) -> PathBuf {
+        root.join(version)
+    }
+
+    // 只查找versions_path中的版本，用于卸载等只能修改一个安装目录的操作
+    fn root_resolver(&self) -> Resolver<'_> {
+        let root = match self.system {
+            true => &self.system_path,
+            false => &self.install_path,
+        };
+
+        Resolver::new(root.join(VERSIONS_DIR), root.join("current"))
+    }
+
+    fn resolver(&self) -> Resolver<'_> {
+        Resolver::new(self.install_path.join(VERSIONS_DIR), self.current_path()).with_root(
+            self.system_path.join(VERSIONS_DIR),
+            self.system_path.join("current"),
+        )
+    }
 }
 
 impl From<&Cli> for Environment {
     fn from(c: &Cli) -> Self {
         Self {
-            database_path: c.database_path.clone(),
-            download_path: c.download_path.clone(),
-            install_path: c.install_path.clone(),
+            database_path: or_default(&c.database_path, BaseDir::Config),
+            download_path: or_default(&c.download_path, BaseDir::Cache),
+            install_path: or_default(&c.install_path, BaseDir::Data),
+            system_path: c.system_path.clone(),
+            system: match &c.command {
+                Some(Commands::Install(x)) => x.system,
+                Some(Commands::Uninstall(x)) => x.system,
+                _ => false,
+            },
         }
     }
 }
 
+// 参数为空说明默认位置无法确定，如没有home且临时目录中的位置不安全
+fn or_default(path: &Path, dir: BaseDir) -> PathBuf {
+    if !path.as_os_str().is_empty() {
+        return path.to_path_buf();
+    }
+
+    dir.path().expect("无法确定默认位置").join("pgvm")
+}
+
 struct App {
     env: Environment,
     db: Db,
 }
 
+// 在源码上应用补丁后编译的自定义版本，安装为如go1.22.3+ourfix
+struct CustomBuild {
+    suffix: String,
+    patches: Vec<Patch>,
+}
+
+impl CustomBuild {
+    fn new(opt: &Install) -> Result<Option<Self>> {
+        if opt.patch.is_empty() {
+            return Ok(None);
+        }
+
+        let mut patches = Vec::with_capacity(opt.patch.len());
+        for x in &opt.patch {
+            let mut hasher = WriteSha256::new(Sha256::new());
+            io::copy(&mut File::open(x)?, &mut hasher)?;
+
+            patches.push(Patch {
+                path: x.canonicalize()?,
+                sha256: hex::encode(hasher.into_sha256().finish()),
+            });
+        }
+
+        // 没有指定后缀时使用补丁的hash，相同的补丁得到相同的版本名
+        let suffix = match &opt.suffix {
+            Some(x) => x.clone(),
+            None => {
+                let mut hasher = Sha256::new();
+                for x in &patches {
+                    hasher.update(x.sha256.as_bytes());
+                }
+
+                hex::encode(hasher.finish())[..8].to_string()
+            }
+        };
+        resolve::check_name(&suffix)?;
+        if !suffix
+            .chars()
+            .all(|x| x.is_ascii_alphanumeric() || x == '_')
+        {
+            return Err(Error {
+                kind: Reason::InvalidResource,
+                msg: format!("无效的版本后缀: {suffix}，只能包含字母、数字与下划线"),
+            });
+        }
+
+        Ok(Some(Self { suffix, patches }))
+    }
+
+    // 在基础版本名称后加上后缀，如go1.22.3+ourfix
+    fn name(&self, base: &str) -> String {
+        format!("{base}+{}", self.suffix)
+    }
+}
+
+// 安装目录名，自定义版本为如go1.22.3+ourfix
+fn install_name(version: &Version, custom: Option<&CustomBuild>) -> String {
+    match custom {
+        Some(x) => x.name(&version.release().to_string()),
+        None => version.to_string(),
+    }
+}
+
 impl App {
+    fn resolver(&self) -> Resolver<'_> {
+        self.env.resolver().with_aliases(|x| self.db.alias(x))
+    }
+
     fn list(&self, opt: &List) {
         // Pager::new().setup();
 
+        if opt.installed {
+            let active = self
+                .resolver()
+                .resolve_installed(&env::current_dir().expect("获取当前目录失败"))
+                .ok()
+                .map(|x| x.0);
+
+            for x in self.installations().expect("获取已安装版本失败") {
+                let last_used = match x.last_used {
+                    Some(t) => ago(t),
+                    None => String::from("从未"),
+                };
+
+                println!(
+                    "{} {}\t{}/{}\t{}\t安装于{}\t最后使用{}\t{}",
+                    if Some(&x.version) == active.as_ref() {
+                        "*"
+                    } else {
+                        " "
+                    },
+                    x.version,
+                    x.os,
+                    x.arch,
+                    HumanBytes(x.size),
+                    ago(x.installed_at),
+                    last_used,
+                    x.path.as_display(),
+                );
+
+                match &x.source {
+                    InstallSource::Patched { base, patches } => {
+                        println!("\t基于{}，补丁:", base);
+                        for p in patches {
+                            println!("\t  {} {}", &p.sha256[..12], p.path.as_display());
+                        }
+                    }
+                    InstallSource::Adopted {
+                        from,
+                        reference: true,
+                    }
+                    | InstallSource::Linked { target: from } => {
+                        println!("\t链接到{}", from.as_display())
+                    }
+                    InstallSource::Bundle { file } => {
+                        println!("\t来自打包文件{}", file.as_display())
+                    }
+                    _ => {}
+                }
+            }
+
+            return;
+        }
+
         match &opt.mode {
             ShowMode::Version => {
                 for x in self
@@ -68,9 +312,35 @@ impl App {
     }
 
     fn install(&self, opt: &Install) {
-        let version = if let Some(v) = &opt.version {
-            self.db
-                .version(v)
+        if let Some(file) = &opt.file {
+            self.install_file(file, opt, false, None).expect("安装失败");
+            return;
+        }
+
+        if let Some(x) = &opt.from_source {
+            self.install_source(x, opt).expect("安装失败");
+            return;
+        }
+
+        let version = if opt.from_project {
+            let dir = env::current_dir().expect("获取当前目录失败");
+            let requirement = Requirement::find(&dir)
+                .expect("读取go.mod失败")
+                .expect("当前目录不在go项目中");
+            println!("{}要求的go版本", requirement.path.as_display());
+
+            let mut versions = self
+                .db
+                .versions(Some(host_os()), Some(host_arch()))
+                .expect("获取版本列表失败");
+            let version = requirement
+                .best(&versions, |x| x.release())
+                .expect("没有满足要求的go版本");
+            let i = versions.iter().position(|x| x == version).unwrap();
+
+            versions.remove(i)
+        } else if let Some(v) = &opt.version {
+            self.catalog_version(v)
                 .expect("读取数据库失败")
                 .expect("不存在的go版本")
         } else {
@@ -87,14 +357,1150 @@ impl App {
 
         println!("选择的go版本{}", version);
 
-        let mut f = self.open_version(&version).expect("获取go版本文件失败");
+        let path = Environment::version_path_in(&self.env.versions_path(), &version.to_string());
+        if !opt.force && path.is_dir() {
+            println!("go版本{}已安装，使用--force重新安装", version);
+            return;
+        }
+
+        self.install_version(&version, opt.force, !opt.no_cache, None, None)
+            .expect("安装失败");
+    }
+
+    fn exec(self, opt: &Exec) {
+        // 优先按目录确定版本，如latest对应的最新版本，目录中没有时再查找已安装的版本
+        let version = match self.catalog_version(&opt.version).expect("读取数据库失败") {
+            Some(version) => {
+                if !self.env.version_path(&version.to_string()).is_dir() {
+                    eprintln!("安装go版本{}", version);
+
+                    self.install_version(&version, false, true, None, None)
+                        .expect("安装失败");
+                }
+
+                version.to_string()
+            }
+            None => self
+                .resolver()
+                .find_installed(&opt.version)
+                .expect("获取已安装版本失败")
+                .expect("不存在的go版本"),
+        };
+
+        self.touch_installation(&version).expect("更新安装记录失败");
+
+        let activation = Activation {
+            goroot: self.env.version_path(&version).join("go"),
+            gobin: self.env.gobin_path(),
+            version,
+        };
+
+        // 命令可能长时间运行，执行前释放数据库锁，避免阻塞其他pgvm进程
+        drop(self.db);
+
+        // 以命令替换当前进程，退出码与信号原样传递
+        let e = Command::new(&opt.command[0])
+            .args(&opt.command[1..])
+            .env("GOROOT", &activation.goroot)
+            .env("GOBIN", &activation.gobin)
+            .env("PATH", activation.joined_path().expect("生成PATH失败"))
+            .exec();
+        eprintln!("执行{}失败: {}", opt.command[0].to_string_lossy(), e);
+
+        process::exit(127);
+    }
+
+    fn current(&self) {
+        let resolver = self.resolver();
+        let resolved = resolver
+            .resolve(&env::current_dir().expect("获取当前目录失败"))
+            .expect("获取go版本失败");
+
+        match resolved {
+            Some(x) => match resolver
+                .find_installed(&x.spec)
+                .expect("获取已安装版本失败")
+            {
+                Some(v) => println!("{v} (来自{})", x.source),
+                None => println!("{} (来自{}，未安装)", x.spec, x.source),
+            },
+            None => println!("未选择go版本"),
+        }
+    }
+
+    fn which(&self, opt: &Which) {
+        let version = self.resolver().require(None).expect("获取go版本失败");
+        let goroot = self.env.version_path(&version).join("go");
+
+        // go tool下的程序位于pkg/tool/<os>_<arch>中
+        let candidates = [
+            goroot.join("bin").join(&opt.binary),
+            goroot
+                .join("pkg")
+                .join("tool")
+                .join(format!("{}_{}", host_os(), host_arch()))
+                .join(&opt.binary),
+        ];
+        let path = candidates
+            .iter()
+            .find(|x| x.is_file())
+            .expect("当前版本中不存在该程序");
+        let path = fs::canonicalize(path).expect("获取程序路径失败");
+
+        println!("{}", path.as_display());
+    }
+
+    fn alias(&self, opt: &Alias) {
+        match &opt.command {
+            AliasCommand::Set { name, version } => {
+                if !matches!(VersionSpec::parse(name), VersionSpec::Name(_)) {
+                    panic!("别名{name}与版本描述冲突");
+                }
+                if name.contains(char::is_whitespace) || version.contains(char::is_whitespace) {
+                    panic!("别名及版本中不能包含空白字符");
+                }
+
+                self.db.set_alias(name, version).expect("存储别名失败");
+                self.sync_aliases().expect("写入别名文件失败");
+                println!("{name} -> {version}");
+            }
+            AliasCommand::Rm { name } => {
+                if !self.db.remove_alias(name).expect("删除别名失败") {
+                    println!("不存在的别名{name}");
+                }
+                self.sync_aliases().expect("写入别名文件失败");
+            }
+            AliasCommand::List => {
+                for (name, version) in self.db.aliases().expect("获取别名列表失败") {
+                    println!("{name} -> {version}")
+                }
+            }
+        }
+    }
+
+    // 将数据库中的别名写入shim读取的别名文件
+    fn sync_aliases(&self) -> Result<()> {
+        resolve::write_aliases(&self.env.aliases_path(), &self.db.aliases()?)
+    }
+
+    fn uninstall(&self, opt: &Uninstall) {
+        let resolver = self.resolver();
+        let global = resolver.global();
+        let active = resolver
+            .resolve_installed(&env::current_dir().expect("获取当前目录失败"))
+            .ok()
+            .map(|x| x.0);
+
+        // 系统安装位置中的版本只有指定--system时才能卸载，反之亦然
+        let root = self.env.root_resolver().with_aliases(|x| self.db.alias(x));
+
+        let mut freed = 0;
+        for spec in opt.versions.iter() {
+            let version = match root.find_installed(spec).expect("获取已安装版本失败") {
+                Some(x) => x,
+                None => {
+                    match (resolver.find_installed(spec), self.env.system) {
+                        (Ok(Some(x)), true) => println!("go版本{x}不在系统安装位置"),
+                        (Ok(Some(x)), false) => {
+                            println!("go版本{x}位于系统安装位置，使用--system卸载")
+                        }
+                        _ => println!("未安装的go版本{spec}"),
+                    }
+                    continue;
+                }
+            };
+
+            if !opt.force
+                && (Some(&version) == active.as_ref() || Some(&version) == global.as_ref())
+            {
+                println!("go版本{version}正在使用，使用--force强制卸载");
+                continue;
+            }
+
+            let path = Environment::version_path_in(&self.env.versions_path(), &version);
+            // 系统安装位置通常只读，没有权限时不尝试删除
+            let _lock = match FileLock::acquire(lock_path(&path).expect("无效的安装路径")) {
+                Ok(x) => x,
+                Err(e) => {
+                    println!("无法卸载{}中的go版本{version}: {e}", path.as_display());
+                    continue;
+                }
+            };
+            let size = dir_size(&path).unwrap_or_default();
+            // 链接的GOROOT只删除链接本身，不会删除其中的文件
+            fs::remove_dir_all(&path).expect("删除安装目录失败");
+            self.db
+                .remove_installation(&version)
+                .expect("删除安装记录失败");
+            freed += size;
+
+            // 卸载全局版本后current链接失效，另一安装目录中的同名版本不受影响
+            if fs::read_link(self.env.current_path()).ok().as_ref() == Some(&path) {
+                fs::remove_file(self.env.current_path()).expect("删除current链接失败");
+            }
+
+            if opt.purge {
+                for x in self.archive_names(&version) {
+                    let path = self.env.download_path.join(x);
+                    if let Ok(meta) = fs::metadata(&path) {
+                        fs::remove_file(&path).expect("删除安装包失败");
+                        freed += meta.len();
+                    }
+                }
+            }
+
+            println!("已卸载go版本{version}");
+        }
+
+        self.refresh().expect("生成shim失败");
+
+        println!("释放空间{}", HumanBytes(freed));
+    }
+
+    fn adopt(&self, opt: &Adopt) {
+        let mut paths = opt.paths.clone();
+        if opt.scan {
+            let found = goroot::known_locations();
+            if found.is_empty() {
+                println!("没有找到已有的go安装");
+            }
+
+            paths.extend(found);
+        }
+
+        for x in paths {
+            match self.adopt_goroot(&x, opt.mode, opt.force) {
+                Ok(Some(v)) => println!("已收录{}为go版本{}", x.display(), v),
+                Ok(None) => {}
+                Err(e) => eprintln!("收录{}失败: {}", x.display(), e),
+            }
+        }
+
+        self.refresh().expect("生成shim失败");
+    }
+
+    // 按GOROOT中的版本与平台收录到安装目录，已安装相同版本时返回None
+    fn adopt_goroot(&self, path: &Path, mode: AdoptMode, force: bool) -> Result<Option<String>> {
+        let install_path = self.env.versions_path();
+        fs::create_dir_all(&install_path)?;
+
+        let from = path.canonicalize()?;
+        if from.starts_with(install_path.canonicalize()?) {
+            println!("{}已由pgvm管理", path.display());
+            return Ok(None);
+        }
+
+        let info = goroot::inspect(&from)?;
+        if info.release().is_none() {
+            return Err(Error {
+                kind: Reason::InvalidGoRoot,
+                msg: format!("无法识别的go版本{}", info.version),
+            });
+        }
+        let name = format!(
+            "{}.{}-{}",
+            info.version,
+            info.os,
+            goroot::package_arch(&info.arch)
+        );
+
+        let dest = install_path.join(&name);
+        let _install_lock = FileLock::acquire(lock_path(&dest)?)?;
+        if !force && dest.is_dir() {
+            println!("go版本{}已安装，跳过{}", name, path.display());
+            return Ok(None);
+        }
+
+        // 移动时先以链接的方式收录，成功后再移动，失败时不会丢失原目录
+        let staging = install::Staging::new(&dest)?;
+        let goroot = staging.path().join("go");
+        match mode {
+            AdoptMode::Copy => {
+                check_space(&[(&install_path, dir_size(&from)?)])?;
+                // 复制期间其他进程可以访问数据库
+                self.db.release();
+                install::copy_tree(&from, &goroot, &[])?;
+            }
+            AdoptMode::Move | AdoptMode::Reference => {
+                std::os::unix::fs::symlink(&from, &goroot)?;
+            }
+        }
+        staging.commit(&dest, force)?;
+
+        if mode == AdoptMode::Move {
+            if let Err(e) = install::move_tree(&from, &dest.join("go")) {
+                fs::remove_dir_all(&dest)?;
+                return Err(e);
+            }
+        }
+
+        self.db.store_installation(&Installation {
+            version: name.clone(),
+            os: info.os,
+            arch: goroot::package_arch(&info.arch).to_string(),
+            size: dir_size(&dest)?,
+            path: dest,
+            installed_at: unix_now(),
+            sha256: None,
+            last_used: None,
+            source: InstallSource::Adopted {
+                from,
+                reference: mode == AdoptMode::Reference,
+            },
+        })?;
+
+        Ok(Some(name))
+    }
+
+    fn link(&self, opt: &Link) {
+        if let Err(e) = resolve::check_name(&opt.name) {
+            println!("{e}");
+            return;
+        }
+        if resolve::is_reserved_name(&opt.name) {
+            println!(
+                "名称{}会被当作版本号，请使用其他名称，如tip或go1.22.3+local",
+                opt.name
+            );
+            return;
+        }
+
+        let target = opt.path.canonicalize().expect("无效的GOROOT路径");
+        // 从源码编译的开发版本没有VERSION文件，只要求go version可以执行
+        let (version, os, arch) = goroot::go_version(&target).expect("无效的GOROOT");
+
+        let install_path = self.env.versions_path();
+        fs::create_dir_all(&install_path).expect("创建安装目录失败");
+
+        let dest = install_path.join(&opt.name);
+        let _install_lock =
+            FileLock::acquire(lock_path(&dest).expect("无效的名称")).expect("获取安装目录锁失败");
+        if !opt.force && dest.symlink_metadata().is_ok() {
+            println!("{}已存在，使用--force替换", opt.name);
+            return;
+        }
+
+        let staging = install::Staging::new(&dest).expect("创建临时目录失败");
+        std::os::unix::fs::symlink(&target, staging.path().join("go")).expect("创建链接失败");
+        staging.commit(&dest, opt.force).expect("注册失败");
+
+        self.db
+            .store_installation(&Installation {
+                version: opt.name.clone(),
+                os,
+                arch: goroot::package_arch(&arch).to_string(),
+                size: 0,
+                path: dest,
+                installed_at: unix_now(),
+                sha256: None,
+                last_used: None,
+                source: InstallSource::Linked {
+                    target: target.clone(),
+                },
+            })
+            .expect("保存安装记录失败");
+        self.refresh().expect("生成shim失败");
+
+        println!(
+            "已注册{}为{}，go版本{}",
+            target.display(),
+            opt.name,
+            version
+        );
+    }
+
+    fn pack(&self, opt: &Pack) {
+        let name = match opt.output.file_name().and_then(|x| x.to_str()) {
+            Some(x) if x.ends_with(&format!(".{}", bundle::EXTENSION)) => x,
+            _ => {
+                println!("打包文件需要以.{}结尾", bundle::EXTENSION);
+                return;
+            }
+        };
+        if !opt.force && opt.output.symlink_metadata().is_ok() {
+            println!("{}已存在，使用--force覆盖", opt.output.display());
+            return;
+        }
+
+        let version = match self
+            .resolver()
+            .find_installed(&opt.version)
+            .expect("获取已安装版本失败")
+        {
+            Some(x) => x,
+            None => {
+                println!("未安装的go版本{}", opt.version);
+                return;
+            }
+        };
+        let installation = self
+            .installations()
+            .expect("获取安装记录失败")
+            .into_iter()
+            .find(|x| x.version == version)
+            .expect("获取安装记录失败");
+
+        // 链接或引用的安装打包链接指向的内容
+        let goroot = installation
+            .path
+            .join("go")
+            .canonicalize()
+            .expect("无效的GOROOT");
+        let manifest = Manifest::collect(&goroot, &version, &installation.os, &installation.arch)
+            .expect("计算文件hash失败");
+
+        // 先写入临时文件，失败时不会留下不完整的打包文件
+        let part = opt.output.with_file_name(format!(".{name}.part"));
+        let r = File::create(&part)
+            .map_err(Error::from)
+            .and_then(|f| manifest.pack(&goroot, f))
+            .and_then(|_| fs::rename(&part, &opt.output).map_err(Error::from));
+        if r.is_err() {
+            let _ = fs::remove_file(&part);
+        }
+        r.expect("打包失败");
+
+        println!(
+            "已将go版本{}打包到{}，{}个文件，共{}",
+            version,
+            opt.output.display(),
+            manifest.files.len(),
+            HumanBytes(manifest.size)
+        );
+    }
+
+    fn sdk(&self, opt: &Sdk) {
+        let enabled = match opt.command {
+            SdkCommand::Enable => true,
+            SdkCommand::Disable => false,
+            SdkCommand::Sync => self.db.sdk_links().expect("读取数据库失败"),
+        };
+        self.db.set_sdk_links(enabled).expect("保存设置失败");
+        self.sync_sdk(enabled).expect("更新sdk链接失败");
+
+        match (enabled, self.env.sdk_path()) {
+            (true, Some(x)) => println!("已更新{}中的链接", x.as_display()),
+            (true, None) => println!("无法确定用户目录"),
+            (false, _) => println!("sdk链接未启用"),
+        }
+    }
+
+    // 已安装的版本变化后重新生成shim，启用时同时更新~/sdk中的链接
+    fn refresh(&self) -> Result<()> {
+        shim::rehash(self.env.shims_path(), &self.env.versions_paths())?;
+
+        let enabled = self.db.sdk_links()?;
+        if enabled {
+            self.sync_sdk(enabled)?;
+        }
+
+        Ok(())
+    }
+
+    // 当前平台的每个发布版本在~/sdk中对应一个链接，同一版本有多个安装时优先使用官方安装包
+    fn sync_sdk(&self, enabled: bool) -> Result<()> {
+        let sdk = match self.env.sdk_path() {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+
+        let mut links = BTreeMap::new();
+        if enabled {
+            for x in self.installations()? {
+                // 自定义版本没有对应的golang.org/dl
+                if x.os != host_os()
+                    || x.arch != host_arch()
+                    || x.version.contains('+')
+                    || matches!(x.source, InstallSource::Linked { .. })
+                {
+                    continue;
+                }
+
+                if let Some(r) = Release::parse(&x.version) {
+                    let name = r.to_string();
+                    if matches!(x.source, InstallSource::Download) || !links.contains_key(&name) {
+                        links.insert(name, x.path.join("go"));
+                    }
+                }
+            }
+        }
+
+        sdk::sync(sdk, &self.env.versions_paths(), &links)
+    }
+
+    // 已安装版本的记录，先与安装目录同步，保证与磁盘上的内容一致
+    fn installations(&self) -> Result<Vec<Installation>> {
+        let installed = self.resolver().installed()?;
+
+        // 删除目录已不存在的记录
+        for x in self.db.installations()? {
+            if !installed.contains(&x.version) || !x.path.is_dir() {
+                self.db.remove_installation(&x.version)?;
+            }
+        }
+
+        // 补充没有记录的目录
+        for x in installed.iter() {
+            if self.db.installation(x)?.is_some() {
+                continue;
+            }
+
+            let path = self.env.version_path(x);
+            let installed_at = fs::metadata(&path)?
+                .modified()?
+                .duration_since(UNIX_EPOCH)
+                .map(|x| x.as_secs())
+                .unwrap_or_default();
+            let (os, arch) = Installation::platform_of(x)
+                .unwrap_or_else(|| (host_os().to_string(), host_arch().to_string()));
+
+            self.db.store_installation(&Installation {
+                version: x.clone(),
+                os,
+                arch,
+                size: dir_size(&path)?,
+                path,
+                installed_at,
+                sha256: None,
+                last_used: None,
+                source: InstallSource::Unknown,
+            })?;
+        }
+
+        self.db.installations()
+    }
+
+    // 记录版本被使用
+    fn touch_installation(&self, version: &str) -> Result<()> {
+        if let Some(mut x) = self.db.installation(version)? {
+            x.last_used = Some(unix_now());
+            self.db.store_installation(&x)?;
+        }
+
+        Ok(())
+    }
+
+    // 已安装版本可能对应的安装包文件名
+    fn archive_names(&self, version: &str) -> Vec<String> {
+        if let Ok(Some(x)) = self.db.version(version) {
+            return vec![x.name];
+        }
+
+        ["tar.gz", "zip"]
+            .iter()
+            .map(|x| format!("{version}.{x}"))
+            .collect()
+    }
+
+    // 在目录中查找当前平台满足版本描述的最新版本，也可以是别名
+    fn catalog_version(&self, spec: &str) -> Result<Option<Version>> {
+        let spec = match self.db.alias(spec)? {
+            Some(x) => VersionSpec::parse(&x),
+            None => VersionSpec::parse(spec),
+        };
+        for x in spec.names() {
+            if let Some(v) = self.db.version(&x)? {
+                return Ok(Some(v));
+            }
+        }
+
+        let versions: Vec<_> = self
+            .db
+            .versions(Some(host_os()), Some(host_arch()))?
+            .into_iter()
+            .filter(|x| x.os == host_os() && x.arch == host_arch())
+            .collect();
+
+        match spec.select(&versions, |x| x.to_string(), |x| Some(x.release())) {
+            Some(x) => self.db.version(&x.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    // 从本地的安装包安装，版本优先从文件名中解析，否则读取安装包中的VERSION文件
+    fn install_file(
+        &self,
+        file: &Path,
+        opt: &Install,
+        source: bool,
+        custom: Option<&CustomBuild>,
+    ) -> Result<()> {
+        let name = file
+            .file_name()
+            .and_then(|x| x.to_str())
+            .ok_or_else(|| Error {
+                kind: Reason::InvalidResource,
+                msg: format!("无效的安装包路径: {}", file.display()),
+            })?;
+        if !source && name.ends_with(bundle::EXTENSION) {
+            return self.install_bundle(file, opt);
+        }
+        let size = fs::metadata(file)?.len().min(i32::MAX as u64) as i32;
+
+        let version = match online::parse_version(name, size) {
+            Some(x) => x,
+            None => {
+                let suffix = ["tar.gz", "zip"]
+                    .into_iter()
+                    .find(|x| name.ends_with(x))
+                    .ok_or_else(|| Error {
+                        kind: Reason::InvalidResource,
+                        msg: format!("不支持的安装包格式: {name}"),
+                    })?;
+                let text = install::archive_version(&mut File::open(file)?)?;
+                let release = Release::parse(&text).ok_or_else(|| Error {
+                    kind: Reason::InvalidResource,
+                    msg: format!("无法识别安装包中的版本: {text}"),
+                })?;
+
+                // VERSION文件中没有平台信息，按当前平台处理，安装时会再检查
+                let platform = if source {
+                    SOURCE_OS.to_string()
+                } else {
+                    format!("{}-{}", host_os(), host_arch())
+                };
+                online::parse_version(&format!("{release}.{platform}.{suffix}"), size).ok_or_else(
+                    || Error {
+                        kind: Reason::InvalidResource,
+                        msg: format!("无法识别安装包中的版本: {text}"),
+                    },
+                )?
+            }
+        };
+        println!("安装包{}的go版本{}", file.display(), version);
+        if source && !version.is_source() {
+            return Err(Error {
+                kind: Reason::InvalidResource,
+                msg: format!("{}不是源码包", file.display()),
+            });
+        }
+
+        // 校验的hash依次为参数指定的、目录中记录的，离线时无法获取目录中的hash
+        let expected = match &opt.sha256 {
+            Some(x) => Some(x.trim().to_lowercase()),
+            None => match self.db.version(&version.to_string())? {
+                Some(x) => online::expected_sha256(&x).unwrap_or_else(|e| {
+                    println!("无法获取{}的sha256，跳过校验: {}", version, e.msg);
+                    None
+                }),
+                None => None,
+            },
+        };
+
+        let mut hasher = WriteSha256::new(Sha256::new());
+        io::copy(&mut File::open(file)?, &mut hasher)?;
+        let sha256 = hex::encode(hasher.into_sha256().finish());
+        if matches!(&expected, Some(x) if *x != sha256) {
+            return Err(Error {
+                kind: Reason::Hashinconformity,
+                msg: String::from("sha256 hash不一致"),
+            });
+        }
+
+        // 复制到下载缓存中，之后与下载的安装包一样安装
+        fs::create_dir_all(&self.env.download_path)?;
+        let archive = self.env.download_path.join(&version.name);
+        {
+            let _download_lock = FileLock::acquire(lock_path(&archive)?)?;
+            let same = matches!(
+                (file.canonicalize(), archive.canonicalize()),
+                (Ok(x), Ok(y)) if x == y
+            );
+            if !same {
+                check_space(&[(&self.env.download_path, size as u64)])?;
+                self.db.release();
+
+                let part = self
+                    .env
+                    .download_path
+                    .join(format!("{}.part", version.name));
+                fs::copy(file, &part)?;
+                fs::rename(&part, &archive)?;
+            }
+        }
+
+        let name = install_name(&version, custom);
+        let path = Environment::version_path_in(&self.env.versions_path(), &name);
+        if !opt.force && path.is_dir() {
+            println!("go版本{}已安装，使用--force重新安装", name);
+            return Ok(());
+        }
+
+        self.install_version(&version, opt.force, true, Some(&sha256), custom)
+    }
+
+    // 从pack生成的打包文件安装，名称与平台来自其中的清单，解压后按清单检查每个文件
+    fn install_bundle(&self, file: &Path, opt: &Install) -> Result<()> {
+        let manifest = bundle::read_manifest(File::open(file)?)?;
+        println!("打包文件{}的go版本{}", file.display(), manifest.version);
+
+        let name = &manifest.version;
+        resolve::check_name(name)?;
+        if manifest.os != host_os() || manifest.arch != host_arch() {
+            return Err(Error {
+                kind: Reason::InvalidResource,
+                msg: format!(
+                    "打包文件的平台为{}-{}，当前平台为{}-{}",
+                    manifest.os,
+                    manifest.arch,
+                    host_os(),
+                    host_arch()
+                ),
+            });
+        }
+
+        let mut hasher = WriteSha256::new(Sha256::new());
+        io::copy(&mut File::open(file)?, &mut hasher)?;
+        let sha256 = hex::encode(hasher.into_sha256().finish());
+        if matches!(&opt.sha256, Some(x) if x.trim().to_lowercase() != sha256) {
+            return Err(Error {
+                kind: Reason::Hashinconformity,
+                msg: String::from("sha256 hash不一致"),
+            });
+        }
+
+        let install_path = self.env.versions_path();
+        fs::create_dir_all(&install_path)?;
+
+        let dest = install_path.join(name);
+        let _install_lock = FileLock::acquire(lock_path(&dest)?)?;
+        if !opt.force && dest.is_dir() {
+            println!("go版本{}已安装，使用--force重新安装", name);
+            return Ok(());
+        }
+
+        check_space(&[(&install_path, manifest.size)])?;
+        // 解压期间其他进程可以访问数据库
+        self.db.release();
+        install::install_bundle(File::open(file)?, &dest, opt.force, |x| {
+            let goroot = x.join("go");
+            manifest.verify(&goroot)?;
+            // 链接的开发版本可能没有VERSION文件，只要求go version可以执行
+            goroot::go_version(&goroot).map(|_| ())
+        })?;
+
+        self.db.store_installation(&Installation {
+            version: name.clone(),
+            os: manifest.os.clone(),
+            arch: manifest.arch.clone(),
+            size: dir_size(&dest)?,
+            path: dest,
+            installed_at: unix_now(),
+            sha256: Some(sha256),
+            last_used: None,
+            source: InstallSource::Bundle {
+                file: file.canonicalize()?,
+            },
+        })?;
+
+        self.refresh()
+    }
+
+    // 从源码编译安装，x可以是版本描述、源码包或源码目录
+    fn install_source(&self, x: &str, opt: &Install) -> Result<()> {
+        let custom = CustomBuild::new(opt)?;
+
+        let path = Path::new(x);
+        if path.is_dir() {
+            return self.install_checkout(path, opt.force, custom.as_ref());
+        }
+        if path.is_file() {
+            return self.install_file(path, opt, true, custom.as_ref());
+        }
+
+        let spec = VersionSpec::parse(x);
+        let versions = self.db.source_versions()?;
+        let version = match spec.select(&versions, |x| x.to_string(), |x| Some(x.release())) {
+            Some(x) => self.db.version(&x.to_string())?,
+            None => None,
+        }
+        .ok_or_else(|| Error {
+            kind: Reason::InvalidResource,
+            msg: format!("不存在go版本{spec}的源码包"),
+        })?;
+        println!("选择的go版本{}", version);
+
+        let name = install_name(&version, custom.as_ref());
+        let path = Environment::version_path_in(&self.env.versions_path(), &name);
+        if !opt.force && path.is_dir() {
+            println!("go版本{}已安装，使用--force重新安装", name);
+            return Ok(());
+        }
+
+        self.install_version(&version, opt.force, !opt.no_cache, None, custom.as_ref())
+    }
+
+    // 从本地的源码目录编译安装，目录中需要有VERSION文件以确定版本
+    fn install_checkout(
+        &self,
+        dir: &Path,
+        force: bool,
+        custom: Option<&CustomBuild>,
+    ) -> Result<()> {
+        if !dir.join("src").join("make.bash").is_file() {
+            return Err(Error {
+                kind: Reason::InvalidResource,
+                msg: format!("{}不是go源码目录", dir.display()),
+            });
+        }
 
+        // 发布版本的源码中有VERSION文件，开发版本没有，版本号由make.bash根据git生成
+        let devel = !dir.join("VERSION").exists();
+        let (release, base) = if devel {
+            let release = build::devel_release(dir).ok_or_else(|| Error {
+                kind: Reason::InvalidResource,
+                msg: format!("无法识别{}中开发版本的版本号", dir.display()),
+            })?;
+            let base = build::devel_name(dir, &release);
+            (release, base)
+        } else {
+            let text = goroot::read_version(dir)?;
+            let release = Release::parse(&text).ok_or_else(|| Error {
+                kind: Reason::InvalidResource,
+                msg: format!("无法识别源码目录中的版本: {text}"),
+            })?;
+            let base = format!("{release}.{SOURCE_OS}");
+            (release, base)
+        };
+        // 与源码包编译的自定义版本同名，如go1.22.3+ourfix，开发版本没有发布版本号，使用base
+        let name = match custom {
+            Some(x) if devel => x.name(&base),
+            Some(x) => x.name(&release.to_string()),
+            None => base.clone(),
+        };
+
+        let install_path = self.env.versions_path();
+        fs::create_dir_all(&install_path)?;
+
+        let path = install_path.join(&name);
+        let _install_lock = FileLock::acquire(lock_path(&path)?)?;
+        if !force && path.is_dir() {
+            println!("go版本{}已安装，使用--force重新安装", name);
+            return Ok(());
+        }
+
+        let bootstrap = self.bootstrap(&release)?;
+        check_space(&[(&install_path, dir_size(dir)? * 2)])?;
+        // 编译期间其他进程可以访问数据库，安装目录锁保证同一版本只有一个进程安装
+        self.db.release();
+
+        // 在副本中编译，不修改源码目录
+        let staging = install::Staging::new(&path)?;
+        let goroot = staging.path().join("go");
+        install::copy_tree(dir, &goroot, &[".git"])?;
+        // 开发版本的版本号与go version -m中的信息来自git，编译期间链接到源码目录的.git，
+        // 编译后生成的VERSION.cache保留了版本号
+        let git = goroot.join(".git");
+        if dir.join(".git").exists() {
+            std::os::unix::fs::symlink(dir.canonicalize()?.join(".git"), &git)?;
+        }
+        for x in custom.iter().flat_map(|x| &x.patches) {
+            build::apply_patch(&goroot, &x.path)?;
+        }
+        build::make(&goroot, bootstrap.as_deref())?;
+        if git.symlink_metadata().is_ok() {
+            fs::remove_file(&git)?;
+        }
+        if devel {
+            goroot::validate_devel(&goroot)?;
+        } else {
+            goroot::validate(&goroot, &release, host_os(), host_arch())?;
+        }
+        staging.commit(&path, force)?;
+
+        self.db.store_installation(&Installation {
+            version: name,
+            os: host_os().to_string(),
+            arch: host_arch().to_string(),
+            size: dir_size(&path)?,
+            path,
+            installed_at: unix_now(),
+            sha256: None,
+            last_used: None,
+            source: match custom {
+                Some(x) => InstallSource::Patched {
+                    base,
+                    patches: x.patches.clone(),
+                },
+                None => InstallSource::Source,
+            },
+        })?;
+
+        self.refresh()
+    }
+
+    // 编译源码使用的go，优先使用环境变量GOROOT_BOOTSTRAP，否则从已安装的版本中选择
+    fn bootstrap(&self, target: &Release) -> Result<Option<PathBuf>> {
+        if let Some(x) = env::var_os("GOROOT_BOOTSTRAP") {
+            return Ok(Some(PathBuf::from(x)));
+        }
+
+        let requirement = match build::bootstrap_requirement(target) {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+
+        let installations: Vec<_> = self
+            .installations()?
+            .into_iter()
+            .filter(|x| x.os == host_os() && x.arch == host_arch())
+            .collect();
+        match build::select_bootstrap(target, &installations, |x| Release::parse(&x.version)) {
+            Some(x) => {
+                println!("使用go版本{}编译", x.version);
+
+                Ok(Some(x.path.join("go")))
+            }
+            None => Err(Error {
+                kind: Reason::BuildFailed,
+                msg: format!("没有可用于编译的go版本，需要先安装{requirement}或更新的版本"),
+            }),
+        }
+    }
+
+    // 下载并安装指定版本，sha256为None时从官方获取用于校验的hash，custom只用于源码包
+    fn install_version(
+        &self,
+        version: &Version,
+        force: bool,
+        cache: bool,
+        sha256: Option<&str>,
+        custom: Option<&CustomBuild>,
+    ) -> Result<()> {
         // 创建安装目录
-        let install_path = self.env.install_path.join("_pgvm_versions");
-        fs::create_dir_all(&install_path).expect("创建go安装目录失败");
+        let install_path = self.env.versions_path();
+        fs::create_dir_all(&install_path)?;
+
+        // 同一版本同时只能有一个进程安装
+        let name = install_name(version, custom);
+        let path = install_path.join(&name);
+        let _install_lock = FileLock::acquire(lock_path(&path)?)?;
+        if !force && path.is_dir() {
+            println!("go版本{}已安装", name);
+            return Ok(());
+        }
+
+        // 安装包在下载、校验、解压期间不能被其他进程修改
+        let archive = self.env.download_path.join(&version.name);
+        let _download_lock = FileLock::acquire(lock_path(&archive)?)?;
+
+        // 下载与解压前确认磁盘空间足够，已缓存的安装包不需要再下载
+        let size = version.size.max(0) as u64;
+        let download_size = if archive.is_file() { 0 } else { size };
+        let ratio = if version.is_source() {
+            BUILD_RATIO
+        } else {
+            UNPACK_RATIO
+        };
+        check_space(&[
+            (&self.env.download_path, download_size),
+            (&install_path, size * ratio),
+        ])?;
+
+        // 源码包解压后先编译，编译结果为当前平台
+        let (os, arch, bootstrap) = if version.is_source() {
+            (
+                host_os().to_string(),
+                host_arch().to_string(),
+                self.bootstrap(&version.release())?,
+            )
+        } else {
+            (version.os.clone(), version.arch.clone(), None)
+        };
+        // 下载、解压与编译期间其他进程可以访问数据库，安装目录锁与下载锁保证同一版本只有一个进程处理
+        self.db.release();
+        let validate = |x: &Path| {
+            let goroot = x.join("go");
+            if version.is_source() {
+                for x in custom.iter().flat_map(|x| &x.patches) {
+                    build::apply_patch(&goroot, &x.path)?;
+                }
+                build::make(&goroot, bootstrap.as_deref())?;
+            }
+
+            goroot::validate(&goroot, &version.release(), &os, &arch).map(|_| ())
+        };
 
-        // 将go文件解压进去
-        install::install(&mut f, install_path.join(&version.to_string())).expect("安装失败");
+        let sha256 = match version.compress {
+            Compress::TarGz => {
+                self.install_stream(version, &path, force, cache, sha256, validate)?
+            }
+            Compress::Zip => {
+                let mut f = self.open_version(version)?;
+
+                // 记录安装包的hash
+                f.rewind()?;
+                let mut hasher = WriteSha256::new(Sha256::new());
+                io::copy(&mut f, &mut hasher)?;
+                let sha256 = hex::encode(hasher.into_sha256().finish());
+                f.rewind()?;
+
+                // 将go文件解压进去
+                install::install(&mut f, &path, force, validate)?;
+
+                sha256
+            }
+        };
+
+        self.db.store_installation(&Installation {
+            version: name,
+            os,
+            arch,
+            size: dir_size(&path)?,
+            path,
+            installed_at: unix_now(),
+            sha256: Some(sha256),
+            last_used: None,
+            source: match custom {
+                Some(x) => InstallSource::Patched {
+                    base: version.to_string(),
+                    patches: x.patches.clone(),
+                },
+                None if version.is_source() => InstallSource::Source,
+                None => InstallSource::Download,
+            },
+        })?;
+
+        // 新版本可能带有新的程序
+        self.refresh()
+    }
+
+    // 边下载边解压，同时计算hash并写入缓存，只读取一次安装包
+    fn install_stream<V: Fn(&Path) -> Result<()>>(
+        &self,
+        version: &Version,
+        path: &Path,
+        force: bool,
+        cache: bool,
+        sha256: Option<&str>,
+        validate: V,
+    ) -> Result<String> {
+        let expected = match sha256 {
+            Some(x) => Some(x.to_lowercase()),
+            // 离线时仍可以使用本地缓存，跳过校验
+            None => match online::expected_sha256(version) {
+                Ok(x) => x,
+                Err(e) if matches!(e.kind, Reason::ConnectionFailed) => {
+                    println!("无法获取{}的sha256，跳过校验: {}", version, e.msg);
+                    None
+                }
+                Err(e) => return Err(e),
+            },
+        };
+        let archive = self.env.download_path.join(&version.name);
+
+        // 优先使用本地缓存
+        if let Ok(f) = File::open(&archive) {
+            match install::install_stream(
+                f,
+                io::sink(),
+                path,
+                force,
+                expected.as_deref(),
+                &validate,
+            ) {
+                // 截断或损坏的缓存无法解压，删除后重新下载
+                Err(e) if install::is_corrupt(&e) => {
+                    println!("本地缓存文件: {} 无法使用: {}", archive.as_display(), e.msg);
+                    println!("本地缓存文件: {} 删除", archive.as_display());
+
+                    fs::remove_file(&archive)?;
+                }
+                x => return x,
+            }
+        }
+
+        let (r, size) = open_version(version)?;
+        if !cache {
+            let w = Progress::wrap(io::sink(), size as u64);
+
+            return install::install_stream(r, w, path, force, expected.as_deref(), validate);
+        }
+
+        // 先写入临时文件，安装成功后才作为缓存
+        fs::create_dir_all(&self.env.download_path)?;
+        let part = self
+            .env
+            .download_path
+            .join(format!("{}.part", version.name));
+        let w = Progress::wrap(File::create(&part)?, size as u64);
+
+        match install::install_stream(r, w, path, force, expected.as_deref(), validate) {
+            Ok(x) => {
+                fs::rename(&part, &archive)?;
+
+                Ok(x)
+            }
+            Err(e) => {
+                fs::remove_file(&part)?;
+
+                Err(e)
+            }
+        }
+    }
+
+    fn switch(&self, opt: &Switch) {
+        let resolver = self.resolver();
+        let version = if let Some(v) = &opt.version {
+            resolver
+                .find_installed(v)
+                .expect("获取已安装版本失败")
+                .expect("未安装的go版本")
+        } else if let Some(x) =
+            Resolver::version_file(&env::current_dir().expect("获取当前目录失败"))
+                .expect("读取版本文件失败")
+        {
+            // 优先使用当前目录指定的版本
+            println!("使用{}指定的go版本{}", x.source, x.spec);
+
+            resolver
+                .find_installed(&x.spec)
+                .expect("获取已安装版本失败")
+                .expect("未安装的go版本")
+        } else {
+            let mut versions = resolver.installed().expect("获取已安装版本失败");
+            if versions.is_empty() {
+                println!("没有已安装的go版本");
+                return;
+            }
+
+            let selections = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("选择go版本")
+                .default(0)
+                .items(&versions[..])
+                .interact()
+                .expect("获取版本选项失败");
+
+            versions.remove(selections)
+        };
+
+        switch::switch(self.env.version_path(&version), self.env.current_path())
+            .expect("切换版本失败");
+        self.touch_installation(&version).expect("更新安装记录失败");
+
+        println!("当前go版本{}", version);
+    }
+
+    fn env(&self, opt: &cli::Env) {
+        let version = self
+            .resolver()
+            .require(opt.version.as_deref())
+            .expect("获取go版本失败");
+
+        let activation = Activation {
+            goroot: self.env.version_path(&version).join("go"),
+            gobin: self.env.gobin_path(),
+            version,
+        };
+
+        if opt.json {
+            println!("{}", activation.render_json().expect("生成环境变量失败"));
+        } else {
+            print!(
+                "{}",
+                activation.render(opt.shell.unwrap_or_else(shell::detect))
+            );
+        }
     }
 
     fn open_version(&self, v: &Version) -> Result<File> {
@@ -143,6 +1549,7 @@ impl App {
             .or_else(|e| {
                 if matches!(e.kind(), io::ErrorKind::NotFound) {
                     let mut f = OpenOptions::new()
+                        .read(true)
                         .write(true)
                         .truncate(true)
                         .create(true)
@@ -163,12 +1570,37 @@ impl App {
 }
 
 fn main() {
-    let cli: Cli = Cli::parse();
+    let args: Vec<_> = env::args_os().collect();
+
+    // 通过shim调用，如go、gofmt
+    let shim_env: Environment = (&Cli::parse_from([env!("CARGO_PKG_NAME")])).into();
+    if let Some(name) = args
+        .first()
+        .and_then(|x| shim::invoked_name(x, &shim_env.shims_path()))
+    {
+        run_shim(&shim_env, &name, &args[1..]);
+    }
+
+    let cli: Cli = Cli::parse_from(args);
 
     let env: Environment = (&cli).into();
+    if let Some(Commands::Shim(x)) = &cli.command {
+        shim(&env, x);
+        return;
+    }
+    migrate(&env);
+
     let db = Db::new(&env.database_path).expect("创建数据库失败");
+    let renamed = migrate_names(&env, &db);
 
     let mut program_state = db.program_state().unwrap();
+    // 旧版本解析目录时交换了os与arch，这样的目录需要重新获取
+    if db
+        .arch()
+        .is_ok_and(|x| x.iter().any(|x| KNOWN_OS.contains(&x.as_str())))
+    {
+        program_state.has_versions = false;
+    }
     if cli.update || !program_state.has_versions {
         let pb = ProgressBar::new_spinner();
         pb.enable_steady_tick(Duration::from_millis(200));
@@ -179,28 +1611,241 @@ fn main() {
         );
         let started = Instant::now();
 
-        // 更新version
-        db.store(online::get_versions().expect("获取go version失败"))
-            .expect("存储go versions失败");
+        // 更新version，数据库锁保证同时只有一个进程更新
+        match online::get_versions() {
+            Ok(x) => {
+                db.store(x).expect("存储go versions失败");
 
-        pb.finish_and_clear();
-        println!("Update Done in {}", HumanDuration(started.elapsed()));
+                pb.finish_and_clear();
+                println!("Update Done in {}", HumanDuration(started.elapsed()));
 
-        program_state.has_versions = true;
-        db.store_program_state(&program_state)
-            .expect("存储program state失败");
+                program_state.has_versions = true;
+                db.store_program_state(&program_state)
+                    .expect("存储program state失败");
+            }
+            // 离线时仍可以使用本地安装包及已安装的版本，下次运行时再更新
+            Err(e) if !cli.update => {
+                pb.finish_and_clear();
+                eprintln!("获取go version失败，跳过更新: {}", e);
+            }
+            Err(e) => panic!("获取go version失败: {:?}", e),
+        }
     }
 
     let app = App { env, db };
+    // 改名后的目录需要重新生成sdk链接
+    if renamed {
+        app.refresh().expect("生成shim失败");
+    }
+    // 别名文件缺失时从数据库生成，如从保存在数据库中的旧版本升级
+    if !app.env.aliases_path().exists() {
+        app.sync_aliases().expect("写入别名文件失败");
+    }
 
     if let Some(sub) = &cli.command {
         match sub {
             Commands::List(x) => app.list(x),
             Commands::Install(x) => app.install(x),
+            Commands::Switch(x) => app.switch(x),
+            Commands::Env(x) => app.env(x),
+            Commands::Exec(x) => app.exec(x),
+            Commands::Current => app.current(),
+            Commands::Which(x) => app.which(x),
+            Commands::Alias(x) => app.alias(x),
+            Commands::Uninstall(x) => app.uninstall(x),
+            Commands::Adopt(x) => app.adopt(x),
+            Commands::Sdk(x) => app.sdk(x),
+            Commands::Link(x) => app.link(x),
+            Commands::Pack(x) => app.pack(x),
+            Commands::Shim(_) => unreachable!(),
         }
     }
 }
 
+// 将旧版本默认位置中的数据库与安装包移动到XDG目录，只在使用默认位置且新位置不存在时进行
+// 旧的默认安装位置已作为系统安装位置使用，不需要迁移。shim不会进行迁移
+fn migrate(env: &Environment) {
+    let legacy = [
+        (
+            dirs::config_dir(),
+            &env.database_path,
+            &*DEFAULT_DATABASE_PATH,
+        ),
+        (
+            dirs::download_dir(),
+            &env.download_path,
+            &*DEFAULT_DOWNLOAD_PATH,
+        ),
+    ];
+
+    for (old, path, default) in legacy {
+        let old = match old {
+            Some(x) => x.join("pgvm"),
+            None => continue,
+        };
+        if path != default || old == *path || !old.is_dir() || path.symlink_metadata().is_ok() {
+            continue;
+        }
+
+        // 持有新旧位置的锁，其他进程打开的数据库不会被移动
+        let locks = [lock_path(path), lock_path(&old)];
+        let _locks = match locks
+            .iter()
+            .flatten()
+            .map(FileLock::acquire)
+            .collect::<Result<Vec<_>>>()
+        {
+            Ok(x) => x,
+            Err(e) => {
+                eprintln!("迁移{}失败: {}", old.as_display(), e);
+                continue;
+            }
+        };
+        // 等待锁期间其他进程可能已完成迁移
+        if !old.is_dir() || path.symlink_metadata().is_ok() {
+            continue;
+        }
+
+        let r = match path.parent() {
+            Some(x) => fs::create_dir_all(x).map_err(Error::from),
+            None => Ok(()),
+        }
+        .and_then(|_| install::move_tree(&old, path));
+        match r {
+            Ok(_) => {
+                // 旧位置已不再使用，等待锁的进程会发现迁移已完成
+                if let Ok(x) = &locks[1] {
+                    let _ = fs::remove_file(x);
+                }
+                eprintln!("已将{}迁移到{}", old.as_display(), path.as_display())
+            }
+            Err(e) => eprintln!("迁移{}失败: {}", old.as_display(), e),
+        }
+    }
+}
+
+// 旧版本中交换了os与arch，安装目录与记录的名称为如go1.21.3.amd64-linux，
+// 在持有数据库锁时一次性改名，返回是否有目录被改名，
+// 没有写权限的安装目录（如只读的系统安装位置）保持原样，按旧名称读取平台
+fn migrate_names(env: &Environment, db: &Db) -> bool {
+    let mut renamed = false;
+    for root in env.versions_paths().into_iter().filter(|x| writable(x)) {
+        let dir = match fs::read_dir(&root) {
+            Ok(x) => x,
+            Err(_) => continue,
+        };
+
+        for x in dir.flatten() {
+            let old = x.file_name().to_string_lossy().to_string();
+            let new = match Installation::legacy_name(&old) {
+                Some(x) => x,
+                None => continue,
+            };
+
+            match rename_installation(env, db, &root, &old, &new) {
+                Ok(_) => {
+                    renamed = true;
+                    eprintln!("已将go版本{old}改名为{new}");
+                }
+                Err(e) => eprintln!("将go版本{old}改名为{new}失败: {e}"),
+            }
+        }
+    }
+
+    // 未改名的旧目录的记录中同样交换了os与arch
+    for mut x in db.installations().unwrap_or_default() {
+        if Installation::legacy_name(&x.version).is_none() {
+            continue;
+        }
+        if let Some((os, arch)) = Installation::platform_of(&x.version) {
+            if (&os, &arch) != (&x.os, &x.arch) {
+                x.os = os;
+                x.arch = arch;
+                let _ = db.store_installation(&x);
+            }
+        }
+    }
+
+    renamed
+}
+
+// 改名安装目录，并更新安装记录、别名与指向其的current链接
+fn rename_installation(
+    env: &Environment,
+    db: &Db,
+    root: &Path,
+    old: &str,
+    new: &str,
+) -> Result<()> {
+    let from = Environment::version_path_in(root, old);
+    let to = Environment::version_path_in(root, new);
+    let _locks = [lock_path(&from)?, lock_path(&to)?]
+        .into_iter()
+        .map(FileLock::acquire)
+        .collect::<Result<Vec<_>>>()?;
+    if to.symlink_metadata().is_ok() {
+        return Err(Error {
+            kind: Reason::InvalidInstallPath,
+            msg: format!("{}已存在", to.as_display()),
+        });
+    }
+    fs::rename(&from, &to)?;
+
+    if let Some(mut x) = db.installation(old)? {
+        db.remove_installation(old)?;
+        if let Some((os, arch)) = Installation::platform_of(new) {
+            x.os = os;
+            x.arch = arch;
+        }
+        x.version = new.to_string();
+        x.path = to.clone();
+        db.store_installation(&x)?;
+    }
+
+    let mut aliases = db.aliases()?;
+    if aliases.iter().any(|x| x.1 == old) {
+        for x in aliases.iter_mut().filter(|x| x.1 == old) {
+            db.set_alias(&x.0, new)?;
+            x.1 = new.to_string();
+        }
+        resolve::write_aliases(&env.aliases_path(), &aliases)?;
+    }
+
+    for current in [env.current_path(), env.system_path.join("current")] {
+        if fs::read_link(&current).ok().as_ref() == Some(&from) {
+            switch::switch(&to, &current)?;
+        }
+    }
+
+    Ok(())
+}
+
+// shim不需要访问数据库，避免多个shim同时运行时争抢数据库
+fn shim(env: &Environment, opt: &Shim) {
+    if let Some(name) = &opt.name {
+        run_shim(env, name, &opt.args);
+    }
+
+    shim::rehash(env.shims_path(), &env.versions_paths()).expect("生成shim失败");
+
+    println!("shim目录: {}", env.shims_path().as_display());
+    println!("请将该目录加入PATH");
+}
+
+fn run_shim(env: &Environment, name: &str, args: &[OsString]) -> ! {
+    // 别名从别名文件中读取，不打开数据库
+    let e = env
+        .resolver()
+        .with_aliases(|x| Ok(resolve::read_aliases(&env.aliases_path())?.remove(x)))
+        .require(None)
+        .and_then(|x| shim::exec(env.version_path(&x).join("go"), name, args))
+        .unwrap_err();
+    eprintln!("pgvm: {e}");
+
+    process::exit(1)
+}
+
+// 距离unix时间戳t过去的时间
 struct Progress<W> {
     inner: W,
     bar: ProgressBar,
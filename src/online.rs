@@ -9,7 +9,7 @@ use serde_xml_rs::from_str;
 use static_init::dynamic;
 use ureq::Agent;
 
-use crate::data::{Compress, UnstableVersion, Version};
+use crate::data::{Compress, UnstableVersion, Version, SOURCE_OS};
 use crate::errors::{Error, Reason, Result};
 
 // 下载地址链接
@@ -17,8 +17,9 @@ const GO_DOWNLOAD_LINK: &str = r#"https://storage.googleapis.com/golang"#;
 // 获取版本信息链接
 const GO_HISTORY_VERSION: &str = r#"https://storage.googleapis.com/golang/?prefix=go&marker="#;
 
-// 匹配go版本正则
-const GO_VERSION_MATCH: &str = r#"go(\d+)(?:\.(\d+))?(?:\.(\d+))?(\w+)?\.(\w+)-(\w+)\.([\w|\.]+)"#;
+// 匹配go版本正则，源码包没有arch，如go1.21.3.src.tar.gz
+const GO_VERSION_MATCH: &str =
+    r#"go(\d+)(?:\.(\d+))?(?:\.(\d+))?(\w+)?\.(\w+)(?:-(\w+))?\.([\w|\.]+)"#;
 
 // 能下载的包格式
 const ALLOW_PACKAGE_SUFFIX: &[&str] = &["tar.gz", "zip"];
@@ -31,7 +32,7 @@ static HTTP_AGENT: Agent = Agent::new();
 
 // go版本匹配
 #[dynamic]
-static GO_VERSION_MATCHER: Regex = Regex::new(GO_VERSION_MATCH).unwrap();
+pub static GO_VERSION_MATCHER: Regex = Regex::new(GO_VERSION_MATCH).unwrap();
 
 // 匹配附加版本
 #[dynamic]
@@ -54,7 +55,7 @@ struct Content {
 }
 
 pub fn open_version(v: &Version) -> Result<(Box<dyn Read>, i32)> {
-    let resp = ureq::get(&vec![GO_DOWNLOAD_LINK, &v.name].join("/")).call()?;
+    let resp = ureq::get(&[GO_DOWNLOAD_LINK, &v.name].join("/")).call()?;
     if resp.status() != 200 {
         return Err(Error {
             kind: Reason::ConnectionFailed,
@@ -80,14 +81,23 @@ pub fn open_version(v: &Version) -> Result<(Box<dyn Read>, i32)> {
     Ok((Box::new(resp.into_reader()), cl))
 }
 
-pub fn verify_version(v: &Version, mut r: impl Read) -> Result<()> {
-    let sha256_link = vec![GO_DOWNLOAD_LINK, &v.sha256].join("/");
-    let resp = ureq::get(&sha256_link).call()?;
-    if resp.status() == 404 {
-        return Ok(());
-    }
+// 官方提供的sha256，没有校验文件时返回None
+pub fn expected_sha256(v: &Version) -> Result<Option<String>> {
+    let sha256_link = [GO_DOWNLOAD_LINK, &v.sha256].join("/");
+    let resp = match ureq::get(&sha256_link).call() {
+        Ok(x) => x,
+        Err(ureq::Error::Status(404, _)) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
 
-    let origin_hash_code = resp.into_string()?.to_lowercase();
+    Ok(Some(resp.into_string()?.trim().to_lowercase()))
+}
+
+pub fn verify_version(v: &Version, mut r: impl Read) -> Result<()> {
+    let origin_hash_code = match expected_sha256(v)? {
+        Some(x) => x,
+        None => return Ok(()),
+    };
 
     let mut hasher = WriteSha256::new(Sha256::new());
     io::copy(&mut r, &mut hasher)?;
@@ -131,16 +141,23 @@ pub fn get_versions() -> Result<Vec<Version>> {
 fn contents_copy_version(contents: Vec<Content>, out: &mut Vec<Version>) {
     contents
         .iter()
+        .flat_map(|x| parse_version(&x.key, x.size))
+        .for_each(|x| out.push(x))
+}
+
+// 从安装包的文件名中解析版本，如go1.21.3.linux-amd64.tar.gz
+pub fn parse_version(name: &str, size: i32) -> Option<Version> {
+    Some(name)
         .filter(|x| {
             ALLOW_PACKAGE_SUFFIX
                 .iter()
-                .any(|suffix| x.key.ends_with(suffix))
+                .any(|suffix| x.ends_with(suffix))
         })
-        .map(|x| (&x.key, x.size, GO_VERSION_MATCHER.captures(&x.key)))
+        .map(|x| (x, size, GO_VERSION_MATCHER.captures(x)))
         .filter(|x| x.2.is_some())
         .map(|x| (x.0, x.1, x.2.unwrap()))
-        .flat_map(|x| {
-            let name = x.0.clone();
+        .and_then(|x| {
+            let name = x.0.to_string();
             let size = x.1;
             let v1: i32 = x.2.get(1)?.as_str().parse().ok()?;
             let v2: Option<i32> = x.2.get(2).and_then(|x| x.as_str().parse().ok());
@@ -161,8 +178,12 @@ fn contents_copy_version(contents: Vec<Content>, out: &mut Vec<Version>) {
 
                         Some(v)
                     });
-            let arch = x.2.get(5).unwrap().as_str().to_string();
-            let os = x.2.get(6).unwrap().as_str().to_string();
+            let os = x.2.get(5).unwrap().as_str().to_string();
+            let arch = match x.2.get(6) {
+                Some(x) => x.as_str().to_string(),
+                None if os == SOURCE_OS => String::new(),
+                None => return None,
+            };
             let compress = {
                 const TAR_GZ: &str = ALLOW_PACKAGE_SUFFIX[0];
                 const ZIP: &str = ALLOW_PACKAGE_SUFFIX[1];
@@ -189,7 +210,6 @@ fn contents_copy_version(contents: Vec<Content>, out: &mut Vec<Version>) {
                 compress,
             })
         })
-        .for_each(|x| out.push(x))
 }
 
 fn get_list_bucket_result(url: &str) -> Result<ListBucket> {
@@ -209,3 +229,26 @@ fn get(url: &str) -> Result<String> {
 fn content_length(resp: &ureq::Response) -> Option<i32> {
     resp.header("content-length").and_then(|x| x.parse().ok())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::online::parse_version;
+
+    #[test]
+    fn parse() {
+        let name = |x: &str| parse_version(x, 0).map(|x| x.to_string());
+
+        assert_eq!(
+            name("go1.21.3.linux-amd64.tar.gz").as_deref(),
+            Some("go1.21.3.linux-amd64")
+        );
+        assert_eq!(
+            name("go1.22rc1.darwin-arm64.zip").as_deref(),
+            Some("go1.22rc1.darwin-arm64")
+        );
+        assert_eq!(name("go1.21.3.src.tar.gz").as_deref(), Some("go1.21.3.src"));
+        assert!(parse_version("go1.21.3.src.tar.gz", 0).unwrap().is_source());
+        assert_eq!(name("go1.21.3.linux.tar.gz"), None);
+        assert_eq!(name("go1.21.3.windows-amd64.msi"), None);
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::{env, fs, io, process};
+
+use crate::data::{cmp_version_name, Release};
+use crate::errors::{Error, Reason, Result};
+use crate::gomod::Requirement;
+use crate::spec::VersionSpec;
+
+// 指定使用的go版本，优先级最高
+pub const VERSION_ENV: &str = "PGVM_VERSION";
+// 目录级别的版本文件
+pub const VERSION_FILE: &str = ".go-version";
+// 安装目录中的别名文件，shim通过它展开别名，不需要打开数据库
+pub const ALIAS_FILE: &str = "aliases";
+
+// 版本选择的来源
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Env,
+    File(PathBuf),
+    Module(PathBuf),
+    Global,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Env => write!(f, "环境变量{VERSION_ENV}"),
+            Source::File(x) | Source::Module(x) => write!(f, "{}", x.display()),
+            Source::Global => write!(f, "全局默认版本"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Resolved {
+    // 请求的版本，可能还不是已安装的版本名
+    pub spec: String,
+    pub source: Source,
+}
+
+type AliasLookup<'a> = Box<dyn Fn(&str) -> Result<Option<String>> + 'a>;
+
+pub struct Resolver<'a> {
+    // 安装目录，同名的版本使用靠前目录中的
+    versions: Vec<PathBuf>,
+    // 全局默认版本的链接，依次查找
+    current: Vec<PathBuf>,
+    aliases: Option<AliasLookup<'a>>,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(versions: impl Into<PathBuf>, current: impl Into<PathBuf>) -> Self {
+        Self {
+            versions: vec![versions.into()],
+            current: vec![current.into()],
+            aliases: None,
+        }
+    }
+
+    // 增加优先级更低的安装目录，如只读的系统安装目录
+    pub fn with_root(mut self, versions: impl Into<PathBuf>, current: impl Into<PathBuf>) -> Self {
+        let versions = versions.into();
+        if !self.versions.contains(&versions) {
+            self.versions.push(versions);
+            self.current.push(current.into());
+        }
+
+        self
+    }
+
+    // 设置别名的查找方式，版本描述无法直接匹配时会尝试作为别名展开
+    pub fn with_aliases(mut self, f: impl Fn(&str) -> Result<Option<String>> + 'a) -> Self {
+        self.aliases = Some(Box::new(f));
+
+        self
+    }
+
+    pub fn alias(&self, name: &str) -> Result<Option<String>> {
+        match &self.aliases {
+            Some(f) => f(name.trim()),
+            None => Ok(None),
+        }
+    }
+
+    // 获取需要使用的已安装版本，指定版本时直接匹配，否则从当前目录开始查找
+    pub fn require(&self, spec: Option<&str>) -> Result<String> {
+        if let Some(spec) = spec {
+            return self.find_installed(spec)?.ok_or_else(|| Error {
+                kind: Reason::VersionNotInstalled,
+                msg: format!("未安装的go版本{spec}"),
+            });
+        }
+
+        Ok(self.resolve_installed(&env::current_dir()?)?.0)
+    }
+
+    // 查找dir下需要使用的版本，依次为环境变量、最近的.go-version文件、go.mod、全局默认版本
+    pub fn resolve(&self, dir: &Path) -> Result<Option<Resolved>> {
+        if let Ok(x) = env::var(VERSION_ENV) {
+            if !x.is_empty() {
+                return Ok(Some(Resolved {
+                    spec: x,
+                    source: Source::Env,
+                }));
+            }
+        }
+
+        if let Some(x) = Self::version_file(dir)? {
+            return Ok(Some(x));
+        }
+
+        if let Some(x) = Requirement::find(dir)? {
+            if let Some(preferred) = x.preferred() {
+                // 已安装的版本中有满足要求的则直接使用，否则要求安装建议的版本
+                let installed: Vec<_> = self
+                    .installed()?
+                    .into_iter()
+                    .filter(|x| release_of(x).is_some())
+                    .collect();
+                let spec = match x.best(&installed, |x| release_of(x).unwrap()) {
+                    Some(v) => v.clone(),
+                    None => preferred.to_string(),
+                };
+
+                return Ok(Some(Resolved {
+                    spec,
+                    source: Source::Module(x.path),
+                }));
+            }
+        }
+
+        Ok(self.global().map(|spec| Resolved {
+            spec,
+            source: Source::Global,
+        }))
+    }
+
+    // 查找并确认版本已安装，返回已安装的版本名
+    pub fn resolve_installed(&self, dir: &Path) -> Result<(String, Source)> {
+        let resolved = self.resolve(dir)?.ok_or_else(|| Error {
+            kind: Reason::VersionNotInstalled,
+            msg: String::from("未选择go版本，请先使用switch切换版本"),
+        })?;
+
+        let version = self.find_installed(&resolved.spec)?.ok_or_else(|| Error {
+            kind: Reason::VersionNotInstalled,
+            msg: format!("未安装的go版本{}，来自{}", resolved.spec, resolved.source),
+        })?;
+
+        Ok((version, resolved.source))
+    }
+
+    // 全局默认版本，即current链接指向的版本，用户没有设置时使用系统的
+    pub fn global(&self) -> Option<String> {
+        let target = self.current.iter().find_map(|x| fs::read_link(x).ok())?;
+
+        target.file_name()?.to_str().map(|x| x.to_string())
+    }
+
+    // 已安装版本所在的目录
+    pub fn path_of(&self, version: &str) -> Option<PathBuf> {
+        self.versions
+            .iter()
+            .map(|x| x.join(version))
+            .find(|x| x.is_dir())
+    }
+
+    // 将版本描述匹配到已安装的版本名，如1.21、go1.21.3、latest、go1.21.3.linux-amd64
+    pub fn find_installed(&self, spec: &str) -> Result<Option<String>> {
+        let installed = self.installed()?;
+        let select = |spec: &VersionSpec| {
+            spec.select(&installed, |x| x.clone(), |x| release_of(x))
+                .cloned()
+        };
+
+        let parsed = VersionSpec::parse(spec);
+        if let Some(x) = select(&parsed) {
+            return Ok(Some(x));
+        }
+
+        if let VersionSpec::Name(_) = parsed {
+            if let Some(x) = self.alias(spec)? {
+                return Ok(select(&VersionSpec::parse(&x)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    // 已安装的go版本，即各安装目录下的子目录
+    pub fn installed(&self) -> Result<Vec<String>> {
+        let mut versions = Vec::new();
+
+        for dir in self.versions.iter() {
+            let dir = match fs::read_dir(dir) {
+                Ok(x) => x,
+                Err(e) if matches!(e.kind(), io::ErrorKind::NotFound) => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            for x in dir {
+                let x = x?;
+                if !x.file_type()?.is_dir() {
+                    continue;
+                }
+
+                if let Some(name) = x.file_name().to_str() {
+                    // 跳过未完成的安装与备份
+                    if check_name(name).is_err() {
+                        continue;
+                    }
+
+                    versions.push(name.to_string());
+                }
+            }
+        }
+
+        versions.sort_by(|x, y| cmp_version_name(y, x));
+        versions.dedup();
+
+        Ok(versions)
+    }
+
+    // 从dir开始向上查找最近的.go-version文件
+    pub fn version_file(dir: &Path) -> Result<Option<Resolved>> {
+        for x in dir.ancestors() {
+            let path = x.join(VERSION_FILE);
+            let text = match fs::read_to_string(&path) {
+                Ok(x) => x,
+                Err(e) if matches!(e.kind(), io::ErrorKind::NotFound) => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            // 取第一行有效内容，忽略空行和注释
+            let spec = text
+                .lines()
+                .map(|x| x.trim())
+                .find(|x| !x.is_empty() && !x.starts_with('#'));
+            if let Some(spec) = spec {
+                return Ok(Some(Resolved {
+                    spec: spec.to_string(),
+                    source: Source::File(path),
+                }));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+// 已安装版本对应的发布版本，自定义版本如go1.22.3+ourfix只能通过完整名称选择
+fn release_of(name: &str) -> Option<Release> {
+    if name.contains('+') {
+        return None;
+    }
+
+    Release::parse(name)
+}
+
+// 安装目录中的名称不能为空或包含/，以.开头的为安装中的临时目录与锁，以.bak结尾的为备份，都会被跳过
+pub fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.starts_with('.') || name.ends_with(".bak") || name.contains('/') {
+        return Err(Error {
+            kind: Reason::InvalidResource,
+            msg: format!("无效的名称{name}，不能为空、以.开头、以.bak结尾或包含/"),
+        });
+    }
+
+    Ok(())
+}
+
+// 名称会被当作版本描述或发布版本匹配，如latest、1.22、go1.22.3，不能用作链接等自定义安装的名称，
+// 带+后缀的自定义版本只能通过完整名称选择，可以使用
+pub fn is_reserved_name(name: &str) -> bool {
+    !matches!(VersionSpec::parse(name), VersionSpec::Name(_)) || release_of(name).is_some()
+}
+
+// 读取别名文件，每行为别名与版本，以tab分隔，文件不存在时没有别名
+pub fn read_aliases(path: &Path) -> Result<BTreeMap<String, String>> {
+    let text = match fs::read_to_string(path) {
+        Ok(x) => x,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(text
+        .lines()
+        .flat_map(|x| x.split_once('\t'))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect())
+}
+
+// 写入别名文件，先写入临时文件再替换，shim不会读到写入一半的内容
+pub fn write_aliases(path: &Path, aliases: &[(String, String)]) -> Result<()> {
+    let text: String = aliases.iter().map(|(k, v)| format!("{k}\t{v}\n")).collect();
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_file_name(format!(".{ALIAS_FILE}.tmp-{}", process::id()));
+    fs::write(&tmp, text)?;
+    fs::rename(&tmp, path)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resolve::{
+        is_reserved_name, read_aliases, write_aliases, Resolver, Source, ALIAS_FILE, VERSION_FILE,
+    };
+    use std::fs;
+
+    #[test]
+    fn find_installed() {
+        let root = std::env::temp_dir().join(format!("pgvm_installed_{}", std::process::id()));
+        for x in [
+            "go1.22.3+ourfix",
+            "go1.22.3.linux-amd64",
+            "go1.21.5.linux-amd64",
+        ] {
+            fs::create_dir_all(root.join(x)).unwrap();
+        }
+
+        let resolver = Resolver::new(&root, root.join("current"));
+        let find = |x: &str| resolver.find_installed(x).unwrap();
+        assert_eq!(find("1.22").as_deref(), Some("go1.22.3.linux-amd64"));
+        assert_eq!(find("latest").as_deref(), Some("go1.22.3.linux-amd64"));
+        assert_eq!(find("go1.22.3+ourfix").as_deref(), Some("go1.22.3+ourfix"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn roots() {
+        let root = std::env::temp_dir().join(format!("pgvm_roots_{}", std::process::id()));
+        let (user, system) = (root.join("user"), root.join("system"));
+        for x in [
+            user.join("go1.21.5.linux-amd64"),
+            system.join("go1.21.5.linux-amd64"),
+        ] {
+            fs::create_dir_all(x).unwrap();
+        }
+        fs::create_dir_all(system.join("go1.22.0.linux-amd64")).unwrap();
+        std::os::unix::fs::symlink(
+            system.join("go1.22.0.linux-amd64"),
+            root.join("system_current"),
+        )
+        .unwrap();
+
+        let resolver = Resolver::new(&user, root.join("user_current"))
+            .with_root(&system, root.join("system_current"));
+        assert_eq!(
+            resolver.installed().unwrap(),
+            ["go1.22.0.linux-amd64", "go1.21.5.linux-amd64"]
+        );
+        assert_eq!(
+            resolver.path_of("go1.21.5.linux-amd64"),
+            Some(user.join("go1.21.5.linux-amd64"))
+        );
+        assert_eq!(resolver.global().as_deref(), Some("go1.22.0.linux-amd64"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn version_file() {
+        let root = std::env::temp_dir().join(format!("pgvm_resolve_{}", std::process::id()));
+        let nested = root.join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.join(VERSION_FILE), "# pinned\n\n1.21.3\n").unwrap();
+
+        let x = Resolver::version_file(&nested).unwrap().unwrap();
+        assert_eq!(x.spec, "1.21.3");
+        assert_eq!(x.source, Source::File(root.join(VERSION_FILE)));
+
+        fs::write(root.join("a").join(VERSION_FILE), "go1.20.7").unwrap();
+        let x = Resolver::version_file(&nested).unwrap().unwrap();
+        assert_eq!(x.spec, "go1.20.7");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn reserved() {
+        for x in [
+            "latest",
+            "stable",
+            "1",
+            "go1",
+            "1.22",
+            "1.22.x",
+            "go1.22.3",
+            "1.22rc1",
+            "go1.24-devel",
+        ] {
+            assert!(is_reserved_name(x), "{x}");
+        }
+        for x in ["tip", "mytip", "devel-go1.24-abc1234", "go1.22.3+local"] {
+            assert!(!is_reserved_name(x), "{x}");
+        }
+    }
+
+    #[test]
+    fn aliases() {
+        let root = std::env::temp_dir().join(format!("pgvm_aliases_{}", std::process::id()));
+        let path = root.join(ALIAS_FILE);
+        assert!(read_aliases(&path).unwrap().is_empty());
+
+        let aliases = [
+            ("legacy".to_string(), "go1.20.7".to_string()),
+            ("prod".to_string(), "1.21".to_string()),
+        ];
+        write_aliases(&path, &aliases).unwrap();
+        let read = read_aliases(&path).unwrap();
+        assert_eq!(read.get("prod").map(|x| x.as_str()), Some("1.21"));
+        assert_eq!(read.len(), 2);
+
+        let x = Resolver::new(root.join("versions"), root.join("current"))
+            .with_aliases(|x| Ok(read_aliases(&path)?.remove(x)));
+        assert_eq!(x.alias("legacy").unwrap().as_deref(), Some("go1.20.7"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,58 @@
+use pgvm::errors::Result;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+
+// golang.org/dl下载完成后写入GOROOT的标记文件
+const UNPACKED_MARKER: &str = ".unpacked-success";
+
+// 使sdk目录中指向安装目录的链接与links一致，links为版本名到GOROOT的映射，如go1.21.3
+// 不是由pgvm创建的内容不会被修改
+pub fn sync<S: AsRef<Path>>(
+    sdk: S,
+    versions: &[PathBuf],
+    links: &BTreeMap<String, PathBuf>,
+) -> Result<()> {
+    let sdk = sdk.as_ref();
+
+    // 清理已卸载或不再需要的链接
+    if let Ok(dir) = fs::read_dir(sdk) {
+        for x in dir.flatten() {
+            let target = match fs::read_link(x.path()) {
+                Ok(x) if versions.iter().any(|v| x.starts_with(v)) => x,
+                _ => continue,
+            };
+
+            let name = x.file_name().to_string_lossy().to_string();
+            if links.get(&name) != Some(&target) {
+                fs::remove_file(x.path())?;
+            }
+        }
+    }
+
+    if links.is_empty() {
+        return Ok(());
+    }
+    fs::create_dir_all(sdk)?;
+
+    for (name, goroot) in links {
+        let path = sdk.join(name);
+        if path.symlink_metadata().is_err() {
+            symlink(goroot, &path)?;
+        } else if fs::read_link(&path).ok().as_ref() != Some(goroot) {
+            continue;
+        }
+
+        // 通过链接访问的GOROOT可能不可写，如引用的系统安装
+        let marker = goroot.join(UNPACKED_MARKER);
+        if !marker.exists() {
+            if let Err(e) = fs::write(&marker, b"") {
+                eprintln!("写入{}失败: {}", marker.display(), e);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use crate::cli::ShellKind;
+use pgvm::errors::{Error, Reason, Result};
+
+use serde::Serialize;
+use std::env;
+use std::path::PathBuf;
+
+// 激活一个go版本所需的环境变量
+#[derive(Debug, Serialize)]
+pub struct Activation {
+    pub version: String,
+    #[serde(rename = "GOROOT")]
+    pub goroot: PathBuf,
+    #[serde(rename = "GOBIN")]
+    pub gobin: PathBuf,
+}
+
+#[derive(Serialize)]
+struct JsonActivation<'a> {
+    #[serde(flatten)]
+    activation: &'a Activation,
+    #[serde(rename = "PATH")]
+    path: String,
+}
+
+impl Activation {
+    // 需要加入PATH的目录，按优先级排列
+    pub fn paths(&self) -> Vec<PathBuf> {
+        vec![self.goroot.join("bin"), self.gobin.clone()]
+    }
+
+    // 将需要的目录加到当前PATH之前
+    pub fn joined_path(&self) -> Result<String> {
+        let mut paths = self.paths();
+        if let Some(x) = env::var_os("PATH") {
+            paths.extend(env::split_paths(&x));
+        }
+
+        let path = env::join_paths(paths).map_err(|e| Error {
+            kind: Reason::InvalidInstallPath,
+            msg: e.to_string(),
+        })?;
+
+        Ok(path.to_string_lossy().to_string())
+    }
+
+    pub fn render(&self, shell: ShellKind) -> String {
+        let goroot = self.goroot.to_string_lossy();
+        let gobin = self.gobin.to_string_lossy();
+        let paths: Vec<_> = self
+            .paths()
+            .iter()
+            .map(|x| quote(shell, &x.to_string_lossy()))
+            .collect();
+
+        match shell {
+            ShellKind::Bash | ShellKind::Zsh => format!(
+                "export GOROOT={}\nexport GOBIN={}\nexport PATH={}:\"$PATH\"\n",
+                quote(shell, &goroot),
+                quote(shell, &gobin),
+                paths.join(":"),
+            ),
+            ShellKind::Fish => format!(
+                "set -gx GOROOT {}\nset -gx GOBIN {}\nset -gx PATH {} $PATH\n",
+                quote(shell, &goroot),
+                quote(shell, &gobin),
+                paths.join(" "),
+            ),
+            ShellKind::Nushell => format!(
+                "$env.GOROOT = {}\n$env.GOBIN = {}\n$env.PATH = ($env.PATH | split row (char esep) | prepend [{}])\n",
+                quote(shell, &goroot),
+                quote(shell, &gobin),
+                paths.join(", "),
+            ),
+        }
+    }
+
+    pub fn render_json(&self) -> Result<String> {
+        let x = JsonActivation {
+            activation: self,
+            path: self.joined_path()?,
+        };
+
+        Ok(serde_json::to_string_pretty(&x).unwrap())
+    }
+}
+
+// 通过SHELL环境变量推断当前shell
+pub fn detect() -> ShellKind {
+    let shell = env::var("SHELL").unwrap_or_default();
+
+    match shell.rsplit('/').next().unwrap_or_default() {
+        "zsh" => ShellKind::Zsh,
+        "fish" => ShellKind::Fish,
+        "nu" => ShellKind::Nushell,
+        _ => ShellKind::Bash,
+    }
+}
+
+fn quote(shell: ShellKind, s: &str) -> String {
+    match shell {
+        ShellKind::Bash | ShellKind::Zsh => format!("'{}'", s.replace('\'', r#"'\''"#)),
+        ShellKind::Fish => format!("'{}'", s.replace('\\', r"\\").replace('\'', r"\'")),
+        ShellKind::Nushell => format!("\"{}\"", s.replace('\\', r"\\").replace('"', "\\\"")),
+    }
+}
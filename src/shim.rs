@@ -0,0 +1,135 @@
+use pgvm::errors::{Error, Reason, Result};
+use pgvm::resolve;
+
+use std::collections::BTreeSet;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::os::unix::fs::symlink;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// 无论安装了哪些版本都会生成的shim
+const DEFAULT_SHIMS: &[&str] = &["go", "gofmt"];
+
+// 以shim方式被调用时的程序名，如go、gofmt，要求从shims目录中调用或名称在shims目录中存在，
+// 改名后的pgvm（如pgvm-x86_64）仍然按命令行解析
+pub fn invoked_name(arg0: &OsString, shims: &Path) -> Option<String> {
+    let path = Path::new(arg0);
+    let name = path.file_name()?.to_str()?;
+    if name == env!("CARGO_PKG_NAME") {
+        return None;
+    }
+
+    let in_shims = path
+        .parent()
+        .filter(|x| !x.as_os_str().is_empty())
+        .and_then(|x| x.canonicalize().ok())
+        .is_some_and(|x| shims.canonicalize().is_ok_and(|shims| x == shims));
+    if in_shims || shims.join(name).symlink_metadata().is_ok() {
+        Some(name.to_string())
+    } else {
+        None
+    }
+}
+
+// 重新生成shim，各安装目录中每个已安装版本bin目录下的程序都对应一个指向pgvm的链接
+pub fn rehash<S: AsRef<Path>>(shims: S, versions: &[PathBuf]) -> Result<()> {
+    let shims = shims.as_ref();
+    fs::create_dir_all(shims)?;
+
+    let mut names: BTreeSet<String> = DEFAULT_SHIMS.iter().map(|x| x.to_string()).collect();
+    for versions in versions {
+        match fs::read_dir(versions) {
+            Ok(dir) => {
+                // 跳过安装过程中的临时目录与备份
+                for x in dir
+                    .flatten()
+                    .filter(|x| resolve::check_name(&x.file_name().to_string_lossy()).is_ok())
+                {
+                    let bin = match fs::read_dir(x.path().join("go").join("bin")) {
+                        Ok(x) => x,
+                        Err(_) => continue,
+                    };
+
+                    names.extend(bin.flatten().flat_map(|x| x.file_name().into_string().ok()));
+                }
+            }
+            Err(e) if matches!(e.kind(), io::ErrorKind::NotFound) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    // 清理已经不存在的程序对应的shim
+    for x in fs::read_dir(shims)?.flatten() {
+        if let Ok(name) = x.file_name().into_string() {
+            if !names.contains(&name) {
+                fs::remove_file(x.path())?;
+            }
+        }
+    }
+
+    let exe = env::current_exe()?;
+    for name in names.iter() {
+        let path = shims.join(name);
+        if path.symlink_metadata().is_ok() {
+            if fs::read_link(&path).ok().as_ref() == Some(&exe) {
+                continue;
+            }
+
+            fs::remove_file(&path)?;
+        }
+
+        symlink(&exe, &path)?;
+    }
+
+    Ok(())
+}
+
+// 执行指定GOROOT中的程序，成功时不会返回
+pub fn exec<G: AsRef<Path>>(goroot: G, name: &str, args: &[OsString]) -> Result<()> {
+    let goroot = goroot.as_ref();
+    let bin = goroot.join("bin").join(name);
+    if !bin.is_file() {
+        return Err(Error {
+            kind: Reason::VersionNotInstalled,
+            msg: format!("{}中不存在程序{name}", goroot.display()),
+        });
+    }
+
+    let e = Command::new(bin).args(args).env("GOROOT", goroot).exec();
+
+    Err(e.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shim::invoked_name;
+    use std::ffi::OsString;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::process;
+
+    #[test]
+    fn invoked() {
+        let dir = std::env::temp_dir().join(format!("pgvm_shim_{}", process::id()));
+        let shims = dir.join("shims");
+        fs::create_dir_all(&shims).unwrap();
+        symlink("/bin/pgvm", shims.join("go")).unwrap();
+
+        let name = |x: &str| invoked_name(&OsString::from(x), &shims);
+        assert_eq!(name("go"), Some(String::from("go")));
+        assert_eq!(name("/usr/bin/go"), Some(String::from("go")));
+        assert_eq!(
+            name(shims.join("vet").to_str().unwrap()),
+            Some(String::from("vet"))
+        );
+        assert_eq!(name("pgvm"), None);
+        assert_eq!(name("/usr/local/bin/pgvm-x86_64"), None);
+        assert_eq!(name("gofmt"), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,224 @@
+use std::fmt::{Display, Formatter};
+
+use regex::Regex;
+use static_init::dynamic;
+
+use crate::common::{host_arch, host_os};
+use crate::data::{Release, UnstableVersion};
+
+// 匹配不带平台的版本描述，如1.21、1.21.x、1.22rc、1.21.3
+#[dynamic]
+static SPEC_MATCHER: Regex =
+    Regex::new(r#"^(\d+)\.(\d+)(?:\.(x|\d+))?(?:(beta|rc)(\d+)?)?$"#).unwrap();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnstableKind {
+    RC,
+    Beta,
+}
+
+// 用户输入的版本描述
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionSpec {
+    // 最新版本，包括rc与beta
+    Latest,
+    // 最新稳定版本
+    Stable,
+    // 某个次版本中最新的稳定版本，如1.21、1.21.x
+    Minor(i32, i32),
+    // 某个次版本中最新的测试版本，如1.22rc
+    Unstable(i32, i32, UnstableKind),
+    // 确定的版本，如go1.21.3、1.22rc1
+    Exact(Release),
+    // 完整的版本名，如go1.21.3.linux-amd64
+    Name(String),
+}
+
+impl VersionSpec {
+    pub fn parse(s: &str) -> Self {
+        let s = s.trim();
+        match s {
+            "latest" => return Self::Latest,
+            "stable" => return Self::Stable,
+            _ => {}
+        }
+
+        let cap = match SPEC_MATCHER.captures(s.strip_prefix("go").unwrap_or(s)) {
+            Some(x) => x,
+            None => return Self::Name(s.to_string()),
+        };
+
+        // 超出范围的数字无法解析，作为名称处理
+        let (v1, v2): (i32, i32) = match (cap[1].parse(), cap[2].parse()) {
+            (Ok(v1), Ok(v2)) => (v1, v2),
+            _ => return Self::Name(s.to_string()),
+        };
+        let kind = cap.get(4).map(|x| match x.as_str() {
+            "beta" => UnstableKind::Beta,
+            _ => UnstableKind::RC,
+        });
+        let n: Option<i32> = match cap.get(5).map(|x| x.as_str().parse()) {
+            Some(Ok(x)) => Some(x),
+            Some(Err(_)) => return Self::Name(s.to_string()),
+            None => None,
+        };
+
+        match (cap.get(3).map(|x| x.as_str()), kind, n) {
+            (Some("x"), None, _) => Self::Minor(v1, v2),
+            (None, None, _) => Self::Minor(v1, v2),
+            (None, Some(kind), None) => Self::Unstable(v1, v2, kind),
+            (v3, kind, n) => {
+                let unstable_v4 = kind.zip(n).map(|(kind, n)| match kind {
+                    UnstableKind::RC => UnstableVersion::RC(n),
+                    UnstableKind::Beta => UnstableVersion::Beta(n),
+                });
+
+                match v3.map(|x| x.parse()) {
+                    Some(Err(_)) => Self::Name(s.to_string()),
+                    v3 => Self::Exact(Release {
+                        v1,
+                        v2: Some(v2),
+                        v3: v3.map(|x| x.unwrap()),
+                        unstable_v4,
+                    }),
+                }
+            }
+        }
+    }
+
+    pub fn matches(&self, r: &Release) -> bool {
+        match self {
+            VersionSpec::Latest => true,
+            VersionSpec::Stable => r.unstable_v4.is_none(),
+            VersionSpec::Minor(v1, v2) => {
+                r.v1 == *v1 && r.v2 == Some(*v2) && r.unstable_v4.is_none()
+            }
+            VersionSpec::Unstable(v1, v2, kind) => {
+                r.v1 == *v1
+                    && r.v2 == Some(*v2)
+                    && r.v3.is_none()
+                    && matches!(
+                        (kind, &r.unstable_v4),
+                        (UnstableKind::RC, Some(UnstableVersion::RC(_)))
+                            | (UnstableKind::Beta, Some(UnstableVersion::Beta(_)))
+                    )
+            }
+            VersionSpec::Exact(x) => x == r,
+            VersionSpec::Name(_) => false,
+        }
+    }
+
+    // 完整版本名的候选，允许省略go前缀及当前平台
+    pub fn names(&self) -> Vec<String> {
+        let s = match self {
+            VersionSpec::Name(x) => x.clone(),
+            VersionSpec::Exact(x) => x.to_string(),
+            _ => return vec![],
+        };
+
+        vec![
+            s.clone(),
+            format!(
+                "go{}.{}-{}",
+                s.strip_prefix("go").unwrap_or(&s),
+                host_os(),
+                host_arch()
+            ),
+        ]
+    }
+
+    // 从候选中选出满足描述的最新版本，release返回None的候选不参与比较
+    pub fn select<'a, T>(
+        &self,
+        candidates: &'a [T],
+        name: impl Fn(&T) -> String,
+        release: impl Fn(&T) -> Option<Release>,
+    ) -> Option<&'a T> {
+        let names = self.names();
+        if let Some(x) = candidates.iter().find(|x| names.contains(&name(x))) {
+            return Some(x);
+        }
+
+        candidates
+            .iter()
+            .flat_map(|x| release(x).filter(|r| self.matches(r)).map(|r| (r, x)))
+            .max_by(|x, y| x.0.cmp(&y.0))
+            .map(|x| x.1)
+    }
+}
+
+impl Display for VersionSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VersionSpec::Latest => write!(f, "latest"),
+            VersionSpec::Stable => write!(f, "stable"),
+            VersionSpec::Minor(v1, v2) => write!(f, "go{v1}.{v2}.x"),
+            VersionSpec::Unstable(v1, v2, kind) => write!(
+                f,
+                "go{v1}.{v2}{}",
+                match kind {
+                    UnstableKind::RC => "rc",
+                    UnstableKind::Beta => "beta",
+                }
+            ),
+            VersionSpec::Exact(x) => write!(f, "{x}"),
+            VersionSpec::Name(x) => write!(f, "{x}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::Release;
+    use crate::spec::{UnstableKind, VersionSpec};
+
+    #[test]
+    fn parse() {
+        assert_eq!(VersionSpec::parse("latest"), VersionSpec::Latest);
+        assert_eq!(VersionSpec::parse("1.21"), VersionSpec::Minor(1, 21));
+        assert_eq!(VersionSpec::parse("go1.21.x"), VersionSpec::Minor(1, 21));
+        assert_eq!(
+            VersionSpec::parse("1.22rc"),
+            VersionSpec::Unstable(1, 22, UnstableKind::RC)
+        );
+        assert_eq!(
+            VersionSpec::parse("go1.21.3"),
+            VersionSpec::Exact(Release::parse("1.21.3").unwrap())
+        );
+        assert_eq!(
+            VersionSpec::parse("go1.21.3.linux-amd64"),
+            VersionSpec::Name("go1.21.3.linux-amd64".to_string())
+        );
+        for x in [
+            "1.99999999999",
+            "99999999999.1",
+            "1.22rc99999999999",
+            "1.21.99999999999",
+        ] {
+            assert_eq!(VersionSpec::parse(x), VersionSpec::Name(x.to_string()));
+        }
+    }
+
+    #[test]
+    fn select() {
+        let versions = [
+            "go1.22rc2",
+            "go1.22rc1",
+            "go1.21.5",
+            "go1.21.4",
+            "go1.20.12",
+        ];
+        let select = |spec: &str| {
+            VersionSpec::parse(spec)
+                .select(&versions, |x| x.to_string(), |x| Release::parse(x))
+                .copied()
+        };
+
+        assert_eq!(select("latest"), Some("go1.22rc2"));
+        assert_eq!(select("stable"), Some("go1.21.5"));
+        assert_eq!(select("1.20"), Some("go1.20.12"));
+        assert_eq!(select("1.22rc"), Some("go1.22rc2"));
+        assert_eq!(select("1.21.4"), Some("go1.21.4"));
+        assert_eq!(select("1.19"), None);
+    }
+}
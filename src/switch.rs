@@ -0,0 +1,36 @@
+use pgvm::errors::{Error, Reason, Result};
+
+use std::fs::{create_dir_all, remove_file, rename};
+use std::os::unix::fs::symlink;
+use std::path::Path;
+
+// 将link指向target，先创建临时链接，再重命名覆盖旧链接，保证切换过程是原子的
+pub fn switch<T: AsRef<Path>, L: AsRef<Path>>(target: T, link: L) -> Result<()> {
+    let tmp = if let Some(name) = link.as_ref().file_name() {
+        link.as_ref()
+            .with_file_name(format!(".{}.tmp", name.to_str().unwrap()))
+    } else {
+        return Err(Error {
+            kind: Reason::InvalidInstallPath,
+            msg: String::from("无效的链接路径"),
+        });
+    };
+
+    // 清理上次切换失败遗留的临时链接
+    if tmp.symlink_metadata().is_ok() {
+        remove_file(&tmp)?;
+    }
+    // 目标可能位于系统安装位置，此时用户的安装目录还不存在
+    if let Some(parent) = tmp.parent() {
+        create_dir_all(parent)?;
+    }
+
+    symlink(target, &tmp)?;
+    rename(&tmp, link).or_else(|e| {
+        remove_file(&tmp)?;
+
+        Err(e)
+    })?;
+
+    Ok(())
+}
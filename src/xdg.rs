@@ -0,0 +1,134 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, DirBuilder};
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::path::{Path, PathBuf};
+
+use crate::errors::{Error, Reason, Result};
+
+// 各XDG目录的环境变量与未设置时相对于home的默认位置
+pub enum BaseDir {
+    Data,
+    Cache,
+    Config,
+}
+
+impl BaseDir {
+    fn var(&self) -> &'static str {
+        match self {
+            BaseDir::Data => "XDG_DATA_HOME",
+            BaseDir::Cache => "XDG_CACHE_HOME",
+            BaseDir::Config => "XDG_CONFIG_HOME",
+        }
+    }
+
+    fn fallback(&self) -> &'static str {
+        match self {
+            BaseDir::Data => ".local/share",
+            BaseDir::Cache => ".cache",
+            BaseDir::Config => ".config",
+        }
+    }
+
+    // 按XDG规范确定目录，没有home时使用临时目录中当前用户独占的目录
+    pub fn path(&self) -> Result<PathBuf> {
+        if let Some(x) = resolve(self, env::var_os(self.var()), dirs::home_dir().as_deref()) {
+            return Ok(x);
+        }
+
+        let uid = unsafe { libc::getuid() };
+        let base = private_dir(&env::temp_dir().join(format!("pgvm-{uid}")))?;
+
+        Ok(base.join(self.fallback()))
+    }
+}
+
+// 规范要求忽略空值与相对路径
+fn resolve(dir: &BaseDir, value: Option<OsString>, home: Option<&Path>) -> Option<PathBuf> {
+    value
+        .map(PathBuf::from)
+        .filter(|x| x.is_absolute())
+        .or_else(|| home.map(|x| x.join(dir.fallback())))
+}
+
+// 创建只有当前用户可以访问的目录，临时目录中的位置可以被其他用户抢先创建，
+// 已存在时要求不是链接、属于当前用户且权限为0700
+fn private_dir(path: &Path) -> Result<PathBuf> {
+    match DirBuilder::new().mode(0o700).create(path) {
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let meta = fs::symlink_metadata(path)?;
+    let uid = unsafe { libc::getuid() };
+    if !meta.is_dir() || meta.uid() != uid || meta.mode() & 0o077 != 0 {
+        return Err(Error {
+            kind: Reason::InvalidInstallPath,
+            msg: format!(
+                "{}不是当前用户独占的目录，请设置HOME或XDG目录",
+                path.display()
+            ),
+        });
+    }
+
+    Ok(path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xdg::{private_dir, resolve, BaseDir};
+    use std::fs;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+    use std::path::{Path, PathBuf};
+    use std::process;
+
+    #[test]
+    fn base_dir() {
+        let home = Some(Path::new("/home/a"));
+
+        assert_eq!(
+            resolve(&BaseDir::Data, Some("/data".into()), home),
+            Some(PathBuf::from("/data"))
+        );
+        assert_eq!(
+            resolve(&BaseDir::Data, None, home),
+            Some(PathBuf::from("/home/a/.local/share"))
+        );
+        assert_eq!(
+            resolve(&BaseDir::Cache, Some("".into()), home),
+            Some(PathBuf::from("/home/a/.cache"))
+        );
+        assert_eq!(
+            resolve(&BaseDir::Config, Some("relative".into()), home),
+            Some(PathBuf::from("/home/a/.config"))
+        );
+        assert_eq!(resolve(&BaseDir::Config, None, None), None);
+    }
+
+    #[test]
+    fn private() {
+        let dir = std::env::temp_dir().join(format!("pgvm_private_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("new");
+        private_dir(&path).unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o700
+        );
+        private_dir(&path).unwrap();
+
+        let open = dir.join("open");
+        fs::create_dir(&open).unwrap();
+        fs::set_permissions(&open, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(private_dir(&open).is_err());
+
+        let link = dir.join("link");
+        symlink(&path, &link).unwrap();
+        assert!(private_dir(&link).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,4 +1,4 @@
-use pgvm::data::Version;
+use pgvm::data::{Compress, Version};
 
 #[test]
 fn dec() {
@@ -12,6 +12,7 @@ fn dec() {
         unstable_v4: None,
         size: 121323,
         sha256: "1".to_string(),
+        compress: Compress::TarGz,
     };
 
     let v = bincode::serialize(&x).unwrap();
@@ -1,4 +1,4 @@
-use pgvm::data::Version;
+use pgvm::data::{Compress, Version};
 
 #[test]
 fn insert() {
@@ -12,6 +12,7 @@ fn insert() {
         unstable_v4: None,
         size: 121323,
         sha256: "1".to_string(),
+        compress: Compress::TarGz,
     };
 
     let db = sled::open("./data.db").unwrap();